@@ -50,24 +50,31 @@
 //!
 //! // serialize to json
 //! let json = serde_json::to_value(&metadata).unwrap();
+//!
+//! // deserialize from json
+//! let deserialized: ContractMetadata = serde_json::from_value(json).unwrap();
+//! assert_eq!(deserialized, metadata);
 //! ```
 
-use core::fmt::{Display, Formatter, Result as DisplayResult, Write};
+use core::{
+    fmt::{Display, Formatter, Result as DisplayResult, Write},
+    str::FromStr,
+};
 use semver::Version;
-use serde::{Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{Map, Value};
 use url::Url;
 
 const METADATA_VERSION: &str = "0.1.0";
 
 /// Smart contract metadata.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ContractMetadata {
     #[serde(rename = "metadataVersion")]
     metadata_version: semver::Version,
     source: Source,
     contract: Contract,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     user: Option<User>,
     /// Raw JSON of the contract abi metadata, generated during contract compilation.
     #[serde(flatten)]
@@ -97,6 +104,16 @@ impl ContractMetadata {
     pub fn remove_source_wasm_attribute(&mut self) {
         self.source.wasm = None;
     }
+
+    /// Returns the information about the contract source.
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    /// Returns the general contract information.
+    pub fn contract(&self) -> &Contract {
+        &self.contract
+    }
 }
 
 /// Representation of the Wasm code hash.
@@ -112,13 +129,32 @@ impl Serialize for CodeHash {
     }
 }
 
-#[derive(Clone, Debug, Serialize)]
+impl<'de> Deserialize<'de> for CodeHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = deserialize_from_byte_str(deserializer)?;
+        let len = bytes.len();
+        let mut hash = [0u8; 32];
+        if len != hash.len() {
+            return Err(de::Error::invalid_length(len, &"a 32 byte hash"));
+        }
+        hash.copy_from_slice(&bytes);
+        Ok(CodeHash(hash))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Source {
     hash: CodeHash,
     language: SourceLanguage,
     compiler: SourceCompiler,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     wasm: Option<SourceWasm>,
+    /// Extra information about the build environment, e.g. the settings the Wasm was built with.
+    #[serde(rename = "buildInfo", default, skip_serializing_if = "Option::is_none")]
+    build_info: Option<Map<String, Value>>,
 }
 
 impl Source {
@@ -134,12 +170,44 @@ impl Source {
             language,
             compiler,
             wasm,
+            build_info: None,
         }
     }
+
+    /// Sets the extra information about the build environment.
+    pub fn with_build_info(mut self, build_info: Map<String, Value>) -> Self {
+        self.build_info = Some(build_info);
+        self
+    }
+
+    /// Returns the hash of the Wasm code.
+    pub fn hash(&self) -> &CodeHash {
+        &self.hash
+    }
+
+    /// Returns the language the contract is written in.
+    pub fn language(&self) -> &SourceLanguage {
+        &self.language
+    }
+
+    /// Returns the compiler the contract was compiled with.
+    pub fn compiler(&self) -> &SourceCompiler {
+        &self.compiler
+    }
+
+    /// Returns the Wasm code, if it is included.
+    pub fn wasm(&self) -> Option<&SourceWasm> {
+        self.wasm.as_ref()
+    }
+
+    /// Returns the extra information about the build environment, if it is included.
+    pub fn build_info(&self) -> Option<&Map<String, Value>> {
+        self.build_info.as_ref()
+    }
 }
 
 /// The bytes of the compiled Wasm smart contract.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SourceWasm {
     wasm: Vec<u8>,
 }
@@ -149,6 +217,11 @@ impl SourceWasm {
     pub fn new(wasm: Vec<u8>) -> Self {
         SourceWasm { wasm }
     }
+
+    /// Returns the Wasm bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.wasm
+    }
 }
 
 impl Serialize for SourceWasm {
@@ -160,6 +233,15 @@ impl Serialize for SourceWasm {
     }
 }
 
+impl<'de> Deserialize<'de> for SourceWasm {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(SourceWasm::new(deserialize_from_byte_str(deserializer)?))
+    }
+}
+
 impl Display for SourceWasm {
     fn fmt(&self, f: &mut Formatter<'_>) -> DisplayResult {
         write!(f, "0x").expect("failed writing to string");
@@ -171,7 +253,7 @@ impl Display for SourceWasm {
 }
 
 /// The language and version in which a smart contract is written.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SourceLanguage {
     language: Language,
     version: Version,
@@ -182,6 +264,16 @@ impl SourceLanguage {
     pub fn new(language: Language, version: Version) -> Self {
         SourceLanguage { language, version }
     }
+
+    /// Returns the language.
+    pub fn language(&self) -> &Language {
+        &self.language
+    }
+
+    /// Returns the version of the language.
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
 }
 
 impl Serialize for SourceLanguage {
@@ -193,14 +285,35 @@ impl Serialize for SourceLanguage {
     }
 }
 
+impl<'de> Deserialize<'de> for SourceLanguage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
 impl Display for SourceLanguage {
     fn fmt(&self, f: &mut Formatter<'_>) -> DisplayResult {
         write!(f, "{} {}", self.language, self.version)
     }
 }
 
+impl FromStr for SourceLanguage {
+    type Err = String;
+
+    /// Parses the `"<language> <version>"` form produced by the `Display` impl,
+    /// e.g. `"ink! 3.0.0"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (language, version) = split_name_and_version(s)?;
+        Ok(SourceLanguage::new(language.parse()?, version))
+    }
+}
+
 /// The language in which the smart contract is written.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Language {
     Ink,
     Solidity,
@@ -217,8 +330,21 @@ impl Display for Language {
     }
 }
 
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ink!" => Ok(Self::Ink),
+            "Solidity" => Ok(Self::Solidity),
+            "AssemblyScript" => Ok(Self::AssemblyScript),
+            _ => Err(format!("Invalid language '{}'", s)),
+        }
+    }
+}
+
 /// A compiler used to compile a smart contract.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SourceCompiler {
     compiler: Compiler,
     version: Version,
@@ -239,14 +365,45 @@ impl Serialize for SourceCompiler {
     }
 }
 
+impl<'de> Deserialize<'de> for SourceCompiler {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl FromStr for SourceCompiler {
+    type Err = String;
+
+    /// Parses the `"<compiler> <version>"` form produced by the `Display` impl,
+    /// e.g. `"rustc 1.49.0-nightly"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (compiler, version) = split_name_and_version(s)?;
+        Ok(SourceCompiler::new(compiler.parse()?, version))
+    }
+}
+
 impl SourceCompiler {
     pub fn new(compiler: Compiler, version: Version) -> Self {
         SourceCompiler { compiler, version }
     }
+
+    /// Returns the compiler.
+    pub fn compiler(&self) -> &Compiler {
+        &self.compiler
+    }
+
+    /// Returns the version of the compiler.
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
 }
 
 /// Compilers used to compile a smart contract.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Compiler {
     RustC,
     Solang,
@@ -261,21 +418,33 @@ impl Display for Compiler {
     }
 }
 
+impl FromStr for Compiler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rustc" => Ok(Self::RustC),
+            "solang" => Ok(Self::Solang),
+            _ => Err(format!("Invalid compiler '{}'", s)),
+        }
+    }
+}
+
 /// Metadata about a smart contract.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Contract {
     name: String,
     version: Version,
     authors: Vec<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     description: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     documentation: Option<Url>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     repository: Option<Url>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     homepage: Option<Url>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     license: Option<String>,
 }
 
@@ -283,10 +452,20 @@ impl Contract {
     pub fn builder() -> ContractBuilder {
         ContractBuilder::default()
     }
+
+    /// Returns the name of the contract.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the version of the contract.
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
 }
 
 /// Additional user defined metadata, can be any valid json.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct User {
     #[serde(flatten)]
     json: Map<String, Value>,
@@ -462,6 +641,50 @@ where
     serializer.serialize_str(&hex)
 }
 
+/// Deserializes the given hex string with a `0x` prefix into bytes.
+///
+/// The empty string, as produced by [`serialize_as_byte_str`] for empty bytes, is accepted
+/// without the prefix.
+fn deserialize_from_byte_str<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    if s.is_empty() {
+        return Ok(Vec::new());
+    }
+    let hex = s
+        .strip_prefix("0x")
+        .ok_or_else(|| de::Error::custom("Expected a byte string prefixed with `0x`"))?;
+    // `from_str_radix` alone would accept a sign, like in `0x+1`
+    if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(de::Error::custom(format!(
+            "Invalid hex byte string '{}'",
+            s
+        )));
+    }
+    if hex.len() % 2 != 0 {
+        return Err(de::Error::custom("Expected an even number of hex digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(de::Error::custom))
+        .collect()
+}
+
+/// Splits the `"<name> <version>"` form of [`SourceLanguage`] and [`SourceCompiler`].
+fn split_name_and_version(s: &str) -> Result<(&str, Version), String> {
+    let mut parts = s.splitn(2, ' ');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(version)) => {
+            let version = Version::parse(version)
+                .map_err(|err| format!("Invalid version '{}': {}", version, err))?;
+            Ok((name, version))
+        }
+        _ => Err(format!("Expected '<name> <version>', got '{}'", s)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -648,4 +871,151 @@ mod tests {
 
         assert_eq!(json, expected);
     }
+
+    #[test]
+    fn round_trip_with_optional_fields() {
+        let language = SourceLanguage::new(Language::Ink, Version::new(3, 0, 0));
+        let compiler =
+            SourceCompiler::new(Compiler::RustC, Version::parse("1.49.0-nightly").unwrap());
+        let wasm = SourceWasm::new(vec![0u8, 1u8, 2u8]);
+        let source = Source::new(Some(wasm), CodeHash([1u8; 32]), language, compiler);
+        let contract = Contract::builder()
+            .name("incrementer")
+            .version(Version::new(3, 0, 0))
+            .authors(vec!["Parity Technologies <admin@parity.io>"])
+            .description("increment a value")
+            .documentation(Url::parse("http://docs.rs/").unwrap())
+            .repository(Url::parse("http://github.com/paritytech/ink/").unwrap())
+            .homepage(Url::parse("http://example.com/").unwrap())
+            .license("Apache-2.0")
+            .build()
+            .unwrap();
+        let user_json = json! {
+            {
+                "some-user-provided-field": "and-its-value"
+            }
+        };
+        let user = User::new(user_json.as_object().unwrap().clone());
+        let abi_json = json! {
+            {
+                "spec": {},
+                "storage": {},
+                "types": []
+            }
+        }
+        .as_object()
+        .unwrap()
+        .clone();
+        let metadata = ContractMetadata::new(source, contract, Some(user), abi_json);
+
+        let json = serde_json::to_string(&metadata).unwrap();
+        let deserialized: ContractMetadata = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized, metadata);
+    }
+
+    #[test]
+    fn round_trip_without_optional_fields() {
+        let language = SourceLanguage::new(Language::Solidity, Version::new(0, 8, 0));
+        let compiler = SourceCompiler::new(Compiler::Solang, Version::new(0, 1, 7));
+        let source = Source::new(None, CodeHash([0u8; 32]), language, compiler);
+        let contract = Contract::builder()
+            .name("incrementer")
+            .version(Version::new(3, 0, 0))
+            .authors(vec!["Parity Technologies <admin@parity.io>"])
+            .build()
+            .unwrap();
+        let metadata = ContractMetadata::new(source, contract, None, Map::new());
+
+        let json = serde_json::to_string(&metadata).unwrap();
+        let deserialized: ContractMetadata = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized, metadata);
+    }
+
+    #[test]
+    fn deserializes_source_from_json() {
+        let json = json! {
+            {
+                "hash": "0x0101010101010101010101010101010101010101010101010101010101010101",
+                "language": "AssemblyScript 0.17.1",
+                "compiler": "rustc 1.49.0-nightly",
+                "wasm": ""
+            }
+        };
+
+        let source: Source = serde_json::from_value(json).unwrap();
+
+        let expected = Source::new(
+            Some(SourceWasm::new(Vec::new())),
+            CodeHash([1u8; 32]),
+            SourceLanguage::new(Language::AssemblyScript, Version::new(0, 17, 1)),
+            SourceCompiler::new(Compiler::RustC, Version::parse("1.49.0-nightly").unwrap()),
+        );
+        assert_eq!(source, expected);
+    }
+
+    #[test]
+    fn source_with_build_info_round_trips() {
+        let language = SourceLanguage::new(Language::Ink, Version::new(3, 0, 0));
+        let compiler = SourceCompiler::new(Compiler::RustC, Version::parse("1.49.0").unwrap());
+        let build_info = json! {
+            {
+                "maxMemoryPages": 16,
+                "linkArgs": []
+            }
+        };
+        let source = Source::new(None, CodeHash([0u8; 32]), language, compiler)
+            .with_build_info(build_info.as_object().unwrap().clone());
+
+        let json = serde_json::to_value(&source).unwrap();
+        let deserialized: Source = serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(json["buildInfo"], build_info);
+        assert_eq!(deserialized.build_info(), build_info.as_object());
+        assert_eq!(deserialized, source);
+    }
+
+    #[test]
+    fn deserializing_invalid_source_fails() {
+        let source = |hash: &str, language: &str, compiler: &str| {
+            let json = json! {
+                {
+                    "hash": hash,
+                    "language": language,
+                    "compiler": compiler,
+                }
+            };
+            serde_json::from_value::<Source>(json).map_err(|err| err.to_string())
+        };
+        let hash = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+        assert!(source(hash, "ink! 3.0.0", "rustc 1.49.0").is_ok());
+        assert!(source("0x0000", "ink! 3.0.0", "rustc 1.49.0")
+            .unwrap_err()
+            .contains("a 32 byte hash"));
+        assert!(source(&hash[2..], "ink! 3.0.0", "rustc 1.49.0")
+            .unwrap_err()
+            .contains("prefixed with `0x`"));
+        assert!(source("0xzz", "ink! 3.0.0", "rustc 1.49.0")
+            .unwrap_err()
+            .contains("Invalid hex byte string"));
+        assert!(
+            source(&format!("0x+1{}", &hash[4..]), "ink! 3.0.0", "rustc 1.49.0")
+                .unwrap_err()
+                .contains("Invalid hex byte string")
+        );
+        assert!(source(hash, "ink 3.0.0", "rustc 1.49.0")
+            .unwrap_err()
+            .contains("Invalid language 'ink'"));
+        assert!(source(hash, "ink! 3.0", "rustc 1.49.0")
+            .unwrap_err()
+            .contains("Invalid version '3.0'"));
+        assert!(source(hash, "ink! 3.0.0", "rustc")
+            .unwrap_err()
+            .contains("Expected '<name> <version>'"));
+        assert!(source(hash, "ink! 3.0.0", "gcc 10.2.0")
+            .unwrap_err()
+            .contains("Invalid compiler 'gcc'"));
+    }
 }
@@ -0,0 +1,276 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cmd::metadata, crate_metadata::CrateMetadata, rustflags, workspace, BuildOptions, UnstableFlags,
+};
+use anyhow::{Context, Result};
+use blake2::digest::{Update as _, VariableOutput as _};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use walkdir::WalkDir;
+
+/// The directory of the build cache, within the target directory.
+const CACHE_DIR: &str = ".cache";
+
+/// A cached build step, together with the key of the build it belongs to.
+#[derive(Deserialize, Serialize)]
+struct Entry<T> {
+    key: String,
+    value: T,
+}
+
+/// Caches the results of the build steps in the `.cache` of the artifact directory, usually
+/// `target/ink/.cache`, so unchanged steps can be skipped.
+///
+/// The results are keyed by the hash of the contract's sources, the `Cargo.lock`, the cargo
+/// configs and toolchain files, the rustc and ink! versions and the build options. A result is
+/// only returned if the key of the current build matches the one it was stored with.
+pub(crate) struct BuildCache {
+    dir: PathBuf,
+    key: String,
+    force: bool,
+}
+
+impl BuildCache {
+    /// Returns the build cache for the contract, bypassed if `build --force` was given.
+    pub fn new(
+        crate_metadata: &CrateMetadata,
+        build_options: &BuildOptions,
+        unstable_flags: &UnstableFlags,
+    ) -> Result<Self> {
+        let mut hasher = blake2::VarBlake2b::new_keyed(&[], 32);
+        let mut update = |label: &str, data: &[u8]| {
+            hasher.update(label.as_bytes());
+            hasher.update((data.len() as u64).to_le_bytes());
+            hasher.update(data);
+        };
+
+        let config_files = workspace::workspace_config_files(&crate_metadata.cargo_meta);
+        for file in source_files(crate_metadata)?
+            .into_iter()
+            .chain(config_files)
+        {
+            let contents = fs::read(&file).context(format!("Failed to read {}", file.display()))?;
+            update(&file.to_string_lossy(), &contents);
+        }
+        let lockfile = crate_metadata
+            .cargo_meta
+            .workspace_root
+            .as_path()
+            .join("Cargo.lock");
+        if lockfile.exists() {
+            update("Cargo.lock", &fs::read(&lockfile)?);
+        }
+        let rustc = rustc_version::version_meta()?.short_version_string;
+        update("rustc", rustc.as_bytes());
+        update("ink", crate_metadata.ink_version.to_string().as_bytes());
+        update("cargo-contract", env!("CARGO_PKG_VERSION").as_bytes());
+
+        // the settings recorded in the metadata, the ones which do not affect the build
+        // artifacts are not part of the key
+        let build_info = metadata::build_info(&crate_metadata.build_settings);
+        update("build settings", &serde_json::to_vec(&build_info)?);
+        let project_dir = crate_metadata
+            .manifest_path
+            .directory()
+            .unwrap_or_else(|| Path::new("."));
+        let rustflags = rustflags::contract_rustflags(project_dir, &crate_metadata.build_settings)?;
+        update("rustflags", rustflags.as_bytes());
+        update(
+            "original manifest",
+            &[unstable_flags.original_manifest as u8],
+        );
+        // a Wasm built without validation must not be taken for a validated one
+        update("skip validation", &[build_options.skip_validation as u8]);
+
+        let mut key = String::new();
+        hasher.finalize_variable(|hash| key = hex::encode(hash));
+        Ok(BuildCache {
+            dir: crate_metadata.artifact_directory.join(CACHE_DIR),
+            key,
+            force: build_options.force,
+        })
+    }
+
+    /// Returns the cached result of the build step, if it was stored by a build with the same key.
+    pub fn get<T: DeserializeOwned>(&self, step: &str) -> Option<T> {
+        if self.force {
+            return None;
+        }
+        let json = fs::read(self.path(step)).ok()?;
+        let entry: Entry<T> = serde_json::from_slice(&json).ok()?;
+        if entry.key != self.key {
+            return None;
+        }
+        Some(entry.value)
+    }
+
+    /// Stores the result of the build step.
+    pub fn put<T: Serialize>(&self, step: &str, value: T) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .context(format!("Failed to create {}", self.dir.display()))?;
+        let entry = Entry {
+            key: self.key.clone(),
+            value,
+        };
+        let path = self.path(step);
+        fs::write(&path, serde_json::to_vec(&entry)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    fn path(&self, step: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", step))
+    }
+}
+
+/// The kinds of targets which are not part of the contract build.
+const SKIPPED_TARGET_KINDS: [&str; 3] = ["test", "bench", "example"];
+
+/// Returns the files cargo builds the local packages of the workspace from, i.e. of the contract
+/// and its path dependencies, in a stable order.
+///
+/// These are the manifests and the files of the built targets, see [`target_files`]. The
+/// `Cargo.lock` and the cargo configs are added by the caller.
+fn source_files(crate_metadata: &CrateMetadata) -> Result<BTreeSet<PathBuf>> {
+    let target_directory = crate_metadata.cargo_meta.target_directory.as_path();
+    let mut files = BTreeSet::new();
+    for package in &crate_metadata.cargo_meta.packages {
+        if package.source.is_some() {
+            continue;
+        }
+        files.insert(package.manifest_path.clone());
+        let package_dir = package
+            .manifest_path
+            .parent()
+            .expect("the manifest path is a file; qed");
+        let roots = package
+            .targets
+            .iter()
+            .filter(|target| {
+                !target
+                    .kind
+                    .iter()
+                    .any(|kind| SKIPPED_TARGET_KINDS.contains(&kind.as_str()))
+            })
+            .map(|target| target.src_path.as_path());
+        files.extend(target_files(package_dir, roots, target_directory)?);
+    }
+    Ok(files)
+}
+
+/// Returns the files of the targets with the given root files, e.g. `src/lib.rs` or `build.rs`.
+///
+/// The directory of a root file is included as a whole, since modules and files included with
+/// `include_bytes!` are usually found there. A root file in the package directory itself, like
+/// `build.rs`, is included on its own. The target directory and hidden files are skipped.
+fn target_files<'a>(
+    package_dir: &Path,
+    roots: impl Iterator<Item = &'a Path>,
+    target_directory: &Path,
+) -> Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    let mut dirs = BTreeSet::new();
+    for root in roots {
+        match root.parent() {
+            Some(dir) if dir != package_dir => {
+                dirs.insert(dir.to_path_buf());
+            }
+            _ => {
+                files.insert(root.to_path_buf());
+            }
+        }
+    }
+    for dir in dirs {
+        let entries = WalkDir::new(dir).into_iter().filter_entry(|entry| {
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            entry.path() != target_directory && (entry.depth() == 0 || !hidden)
+        });
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                files.insert(entry.into_path());
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::with_tmp_dir;
+
+    fn cache(dir: &Path, key: &str, force: bool) -> BuildCache {
+        BuildCache {
+            dir: dir.join(CACHE_DIR),
+            key: key.to_string(),
+            force,
+        }
+    }
+
+    #[test]
+    fn cached_steps_are_only_returned_for_the_same_key() {
+        with_tmp_dir(|path| {
+            cache(path, "a", false).put("wasm", 42u32)?;
+
+            assert_eq!(cache(path, "a", false).get::<u32>("wasm"), Some(42));
+            assert_eq!(cache(path, "b", false).get::<u32>("wasm"), None);
+            assert_eq!(cache(path, "a", false).get::<u32>("metadata"), None);
+            assert_eq!(cache(path, "a", true).get::<u32>("wasm"), None);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn only_the_files_of_the_built_targets_are_sources() {
+        with_tmp_dir(|path| {
+            let files = [
+                "src/lib.rs",
+                "src/storage/mod.rs",
+                "src/.lib.rs.swp",
+                "build.rs",
+                "README.md",
+                "node_modules/package.json",
+                "target/ink/flipper.wasm",
+            ];
+            for file in &files {
+                let file = path.join(file);
+                fs::create_dir_all(file.parent().unwrap())?;
+                fs::write(file, "")?;
+            }
+            let roots = [path.join("src/lib.rs"), path.join("build.rs")];
+
+            let sources = target_files(
+                path,
+                roots.iter().map(PathBuf::as_path),
+                &path.join("target"),
+            )?;
+
+            let expected = ["build.rs", "src/lib.rs", "src/storage/mod.rs"]
+                .iter()
+                .map(|file| path.join(file))
+                .collect::<BTreeSet<_>>();
+            assert_eq!(sources, expected);
+            Ok(())
+        })
+    }
+}
@@ -18,24 +18,26 @@ use std::{
     convert::TryFrom,
     fs::{metadata, File},
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use crate::{
-    crate_metadata::CrateMetadata,
-    util,
+    build_cache::BuildCache,
+    cmd::{metadata::blake2_hash, size::SizeReport, symbolize::SymbolMap},
+    crate_metadata::{self, CargoFlags, CodeSize, CrateMetadata, OptimizationPasses},
+    rustflags, util, validate_wasm,
     workspace::{ManifestPath, Profile, Workspace},
-    BuildArtifacts, BuildResult, UnstableFlags, UnstableOptions, VerbosityFlags,
+    BuildArtifacts, BuildOptions, BuildResult, BuildSettingsFlags, BuildSettingsOverrides,
+    UnstableFlags, UnstableOptions, VerbosityFlags, WorkspaceBuildResult,
 };
 use crate::{OptimizationResult, Verbosity};
 use anyhow::{Context, Result};
 use colored::Colorize;
+use contract_metadata::CodeHash;
 use parity_wasm::elements::{External, MemoryType, Module, Section};
+use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
-/// This is the maximum number of pages available for a contract to allocate.
-const MAX_MEMORY_PAGES: u32 = 16;
-
 /// Executes build of the smart-contract which produces a wasm binary that is ready for deploying.
 ///
 /// It does so by invoking `cargo build` and then post processing the final binary.
@@ -45,6 +47,12 @@ pub struct BuildCommand {
     /// Path to the Cargo.toml of the contract to build
     #[structopt(long, parse(from_os_str))]
     manifest_path: Option<PathBuf>,
+    /// Build all ink! contracts of the workspace
+    #[structopt(long, conflicts_with = "packages")]
+    workspace: bool,
+    /// Build only the specified ink! contract of the workspace, may be specified multiple times
+    #[structopt(short, long = "package", value_name = "SPEC", number_of_values = 1)]
+    packages: Vec<String>,
     /// Which build artifacts to generate.
     ///
     /// - `all`: Generate the Wasm, the metadata and a bundled `<name>.contract` file.
@@ -58,6 +66,44 @@ pub struct BuildCommand {
         verbatim_doc_comment
     )]
     build_artifact: BuildArtifacts,
+    /// The binaryen optimization passes, like the `-O<level>` flags of `wasm-opt`.
+    ///
+    /// Overrides the `optimization-passes` of `[package.metadata.contract]`, defaults to `3`.
+    /// Also overrides a `skip-optimization = true` of the manifest.
+    #[structopt(long, value_name = "0 | 1 | 2 | 3 | s | z")]
+    optimization_passes: Option<OptimizationPasses>,
+    /// Skip the optimization of the Wasm with binaryen, e.g. for faster builds during development
+    #[structopt(long, conflicts_with = "optimization-passes")]
+    skip_optimization: bool,
+    /// Build with debug assertions and panic messages, keeping the `name` section of the Wasm.
+    ///
+    /// The artifacts are written to `target/ink/debug`, the metadata marks the bundle as a debug
+    /// build.
+    #[structopt(long)]
+    debug: bool,
+    /// Fail if the optimized Wasm is larger than this size, in bytes or like `32K`.
+    ///
+    /// Overrides the `max-code-size` of `[package.metadata.contract]`.
+    #[structopt(long, value_name = "SIZE")]
+    max_size: Option<CodeSize>,
+    /// Rebuild all steps, even if nothing changed since the last build
+    #[structopt(long)]
+    force: bool,
+    /// Skip the validation of the Wasm's imports, exports and instructions, e.g. for a chain with
+    /// additional host functions
+    #[structopt(long)]
+    skip_validation: bool,
+    /// Require the `Cargo.lock` to be up to date, passed through to cargo
+    #[structopt(long)]
+    locked: bool,
+    /// Require the `Cargo.lock` and the cache to be up to date, passed through to cargo
+    #[structopt(long)]
+    frozen: bool,
+    /// Build without accessing the network, passed through to cargo
+    #[structopt(long)]
+    offline: bool,
+    #[structopt(flatten)]
+    build_settings: BuildSettingsFlags,
     #[structopt(flatten)]
     verbosity: VerbosityFlags,
     #[structopt(flatten)]
@@ -75,9 +121,67 @@ impl BuildCommand {
             verbosity,
             true,
             self.build_artifact,
+            &self.build_settings_overrides(),
+            &self.build_options(),
+            unstable_flags,
+        )
+    }
+
+    /// Returns `true` if the contracts of the workspace are built, i.e. if `--workspace` or
+    /// `--package` is given.
+    pub fn builds_workspace(&self) -> bool {
+        self.workspace || !self.packages.is_empty()
+    }
+
+    /// Builds the ink! contracts of the workspace, see [`BuildCommand::builds_workspace`].
+    pub fn exec_workspace(&self) -> Result<WorkspaceBuildResult> {
+        let manifest_path = match &self.manifest_path {
+            Some(path) => ManifestPath::new(path)?,
+            None => ManifestPath::nearest()?,
+        };
+        let unstable_flags: UnstableFlags =
+            TryFrom::<&UnstableOptions>::try_from(&self.unstable_options)?;
+        let verbosity: Option<Verbosity> = TryFrom::<&VerbosityFlags>::try_from(&self.verbosity)?;
+        execute_workspace(
+            &manifest_path,
+            &self.packages,
+            verbosity,
+            self.build_artifact,
+            &self.build_settings_overrides(),
+            &self.build_options(),
             unstable_flags,
         )
     }
+
+    fn build_settings_overrides(&self) -> BuildSettingsOverrides {
+        // explicit optimization passes turn off a `skip-optimization` of the manifest
+        let skip_optimization = if self.skip_optimization {
+            Some(true)
+        } else if self.optimization_passes.is_some() {
+            Some(false)
+        } else {
+            None
+        };
+        BuildSettingsOverrides {
+            flags: self.build_settings.clone(),
+            optimization_passes: self.optimization_passes,
+            skip_optimization,
+            debug: self.debug,
+        }
+    }
+
+    fn build_options(&self) -> BuildOptions {
+        BuildOptions {
+            max_code_size: self.max_size,
+            force: self.force,
+            skip_validation: self.skip_validation,
+            cargo_flags: CargoFlags {
+                locked: self.locked,
+                frozen: self.frozen,
+                offline: self.offline,
+            },
+        }
+    }
 }
 
 #[derive(Debug, StructOpt)]
@@ -87,6 +191,8 @@ pub struct CheckCommand {
     #[structopt(long, parse(from_os_str))]
     manifest_path: Option<PathBuf>,
     #[structopt(flatten)]
+    build_settings: BuildSettingsFlags,
+    #[structopt(flatten)]
     verbosity: VerbosityFlags,
     #[structopt(flatten)]
     unstable_options: UnstableOptions,
@@ -103,6 +209,11 @@ impl CheckCommand {
             verbosity,
             false,
             BuildArtifacts::CheckOnly,
+            &BuildSettingsOverrides {
+                flags: self.build_settings.clone(),
+                ..Default::default()
+            },
+            &BuildOptions::default(),
             unstable_flags,
         )
     }
@@ -127,38 +238,54 @@ fn build_cargo_project(
     crate_metadata: &CrateMetadata,
     build_artifact: BuildArtifacts,
     verbosity: Option<Verbosity>,
+    cargo_flags: CargoFlags,
     unstable_flags: UnstableFlags,
 ) -> Result<()> {
     util::assert_channel()?;
 
-    // set linker args via RUSTFLAGS.
-    // Currently will override user defined RUSTFLAGS from .cargo/config. See https://github.com/paritytech/cargo-contract/issues/98.
-    std::env::set_var(
-        "RUSTFLAGS",
-        "-C link-arg=-z -C link-arg=stack-size=65536 -C link-arg=--import-memory",
-    );
+    // The linker args are passed via CARGO_ENCODED_RUSTFLAGS, merged with the user defined flags. Those have to
+    // be collected from the original project directory, since only the `.cargo/config` files
+    // within the workspace are copied to the temporary workspace.
+    let project_dir = crate_metadata
+        .manifest_path
+        .directory()
+        .unwrap_or_else(|| Path::new("."));
+    let rustflags = rustflags::contract_rustflags(project_dir, &crate_metadata.build_settings)?;
+    log::debug!("Building with rustflags {:?}", rustflags.split('\x1f'));
+    // `RUSTFLAGS` are already merged into the encoded flags
+    let env = || {
+        vec![
+            ("CARGO_ENCODED_RUSTFLAGS", Some(rustflags.as_str())),
+            ("RUSTFLAGS", None),
+        ]
+    };
 
     let cargo_build = |manifest_path: &ManifestPath| {
         let target_dir = &crate_metadata.target_directory;
-        let args = [
+        let target_dir_arg = format!("--target-dir={}", target_dir.to_string_lossy());
+        let mut args = vec![
             "--target=wasm32-unknown-unknown",
             "-Zbuild-std",
-            "-Zbuild-std-features=panic_immediate_abort",
             "--no-default-features",
             "--release",
-            &format!("--target-dir={}", target_dir.to_string_lossy()),
+            &target_dir_arg,
         ];
+        // panic messages are only included in debug builds
+        if !crate_metadata.build_settings.debug {
+            args.push("-Zbuild-std-features=panic_immediate_abort");
+        }
+        args.extend(cargo_flags.args());
         if build_artifact == BuildArtifacts::CheckOnly {
-            util::invoke_cargo("check", &args, manifest_path.directory(), verbosity)?;
+            util::invoke_cargo("check", &args, manifest_path.directory(), verbosity, env())?;
         } else {
-            util::invoke_cargo("build", &args, manifest_path.directory(), verbosity)?;
+            util::invoke_cargo("build", &args, manifest_path.directory(), verbosity, env())?;
         }
 
         Ok(())
     };
 
     if unstable_flags.original_manifest {
-        println!(
+        progress!(
             "{} {}",
             "warning:".yellow().bold(),
             "with 'original-manifest' enabled, the contract binary may not be of optimal size."
@@ -173,12 +300,10 @@ fn build_cargo_project(
                     .with_profile_release_defaults(Profile::default_contract_release())?;
                 Ok(())
             })?
+            .keep_temp(unstable_flags.keep_temp_workspace)
             .using_temp(cargo_build)?;
     }
 
-    // clear RUSTFLAGS
-    std::env::remove_var("RUSTFLAGS");
-
     Ok(())
 }
 
@@ -211,26 +336,56 @@ fn ensure_maximum_memory_pages(module: &mut Module, maximum_allowed_pages: u32)
         }
     } else {
         let initial = mem_ty.limits().initial();
-        *mem_ty = MemoryType::new(initial, Some(MAX_MEMORY_PAGES));
+        *mem_ty = MemoryType::new(initial, Some(maximum_allowed_pages));
     }
 
     Ok(())
 }
 
-/// Strips all custom sections.
+/// Strips all custom sections except the `name` section.
 ///
-/// Presently all custom sections are not required so they can be stripped safely.
-fn strip_custom_sections(module: &mut Module) {
+/// Presently all custom sections are not required so they can be stripped safely. For debug
+/// builds the `producers` section is kept as well. The `name` section is required for the
+/// symbol map and only stripped afterwards, see [`write_symbol_map`].
+fn strip_custom_sections(module: &mut Module, debug: bool) {
     module.sections_mut().retain(|section| match section {
-        Section::Custom(_) => false,
-        Section::Name(_) => false,
+        Section::Custom(custom) => debug && custom.name() == "producers",
+        Section::Name(_) => true,
         Section::Reloc(_) => false,
         _ => true,
     });
 }
 
+/// Strips the `name` section, unless this is a debug build.
+fn strip_name_section(module: &mut Module, debug: bool) {
+    if !debug {
+        module
+            .sections_mut()
+            .retain(|section| !matches!(section, Section::Name(_)));
+    }
+}
+
+/// Writes the symbol map of the post processed Wasm and strips its `name` section, unless this is
+/// a debug build.
+///
+/// The symbol map maps the function indices of the Wasm to the demangled function names, in
+/// order to attribute traps of the stripped Wasm to functions. Binaryen renumbers the functions,
+/// hence this has to run after [`optimize_wasm`].
+fn write_symbol_map(crate_metadata: &CrateMetadata) -> Result<()> {
+    let mut module = parity_wasm::deserialize_file(&crate_metadata.dest_wasm).context(format!(
+        "Loading post processed wasm file '{}'",
+        crate_metadata.dest_wasm.display()
+    ))?;
+    SymbolMap::from_module(&module).save(&crate_metadata.symbol_map_path())?;
+    strip_name_section(&mut module, crate_metadata.build_settings.debug);
+    parity_wasm::serialize_to_file(&crate_metadata.dest_wasm, module)?;
+    Ok(())
+}
+
 /// Performs required post-processing steps on the wasm artifact.
-fn post_process_wasm(crate_metadata: &CrateMetadata) -> Result<()> {
+///
+/// The Wasm is validated unless `skip_validation` is given, see [`validate_wasm::validate`].
+fn post_process_wasm(crate_metadata: &CrateMetadata, skip_validation: bool) -> Result<()> {
     // Deserialize wasm module from a file.
     let mut module =
         parity_wasm::deserialize_file(&crate_metadata.original_wasm).context(format!(
@@ -238,6 +393,11 @@ fn post_process_wasm(crate_metadata: &CrateMetadata) -> Result<()> {
             crate_metadata.original_wasm.display()
         ))?;
 
+    // the optimizer removes all exports but the entrypoints, so those are validated before
+    if !skip_validation {
+        validate_wasm::validate_exports(&module)?;
+    }
+
     // Perform optimization.
     //
     // In practice only tree-shaking is performed, i.e transitively removing all symbols that are
@@ -245,13 +405,47 @@ fn post_process_wasm(crate_metadata: &CrateMetadata) -> Result<()> {
     if pwasm_utils::optimize(&mut module, ["call", "deploy"].to_vec()).is_err() {
         anyhow::bail!("Optimizer failed");
     }
-    ensure_maximum_memory_pages(&mut module, MAX_MEMORY_PAGES)?;
-    strip_custom_sections(&mut module);
+    ensure_maximum_memory_pages(&mut module, crate_metadata.build_settings.max_memory_pages)?;
+    if !skip_validation {
+        validate_wasm::validate(&module)?;
+    }
+    strip_custom_sections(&mut module, crate_metadata.build_settings.debug);
 
     parity_wasm::serialize_to_file(&crate_metadata.dest_wasm, module)?;
     Ok(())
 }
 
+/// Fails if the optimized Wasm is larger than the `max-code-size` of the contract.
+///
+/// The storage deposit of a contract scales with its code size, so this allows to enforce a
+/// budget. On failure the biggest contributors to the code size are printed.
+fn ensure_maximum_code_size(
+    crate_metadata: &CrateMetadata,
+    build_options: &BuildOptions,
+    optimization_result: &OptimizationResult,
+) -> Result<()> {
+    let max_code_size = match build_options.max_code_size.or(crate_metadata.max_code_size) {
+        Some(max_code_size) => max_code_size,
+        None => return Ok(()),
+    };
+    let overshoot = optimization_result.optimized_size - max_code_size.kilobytes();
+    if overshoot <= 0.0 {
+        return Ok(());
+    }
+
+    let report = SizeReport::load(&crate_metadata.dest_wasm, None)?;
+    progress!(
+        "\nThe biggest contributors to the code size are:{}",
+        report.display(10)
+    );
+    anyhow::bail!(
+        "The contract's Wasm is {:.1}K, which exceeds the maximum code size of {:.1}K by {:.0} bytes",
+        optimization_result.optimized_size,
+        max_code_size.kilobytes(),
+        overshoot * 1000.0
+    )
+}
+
 /// Attempts to perform optional wasm optimization using `binaryen`.
 ///
 /// The intention is to reduce the size of bloated wasm binaries as a result of missing
@@ -260,19 +454,24 @@ fn optimize_wasm(crate_metadata: &CrateMetadata) -> Result<OptimizationResult> {
     let mut optimized = crate_metadata.dest_wasm.clone();
     optimized.set_file_name(format!("{}-opt.wasm", crate_metadata.package_name));
 
+    let optimization_passes = crate_metadata.build_settings.optimization_passes;
     let codegen_config = binaryen::CodegenConfig {
-        // execute -O3 optimization passes (spends potentially a lot of time optimizing)
-        optimization_level: 3,
-        // the default
-        shrink_level: 1,
-        // the default
-        debug_info: false,
+        optimization_level: optimization_passes.optimization_level(),
+        shrink_level: optimization_passes.shrink_level(),
+        // keeps the `name` section, which is required for the symbol map
+        debug_info: true,
     };
 
     let mut dest_wasm_file = File::open(crate_metadata.dest_wasm.as_os_str())?;
     let mut dest_wasm_file_content = Vec::new();
     dest_wasm_file.read_to_end(&mut dest_wasm_file_content)?;
 
+    // the `name` section is stripped only after the optimization, it must not count towards
+    // the original size either
+    let mut original_module: Module = parity_wasm::deserialize_buffer(&dest_wasm_file_content)?;
+    strip_name_section(&mut original_module, crate_metadata.build_settings.debug);
+    let original_size = original_module.to_bytes()?.len() as f64 / 1000.0;
+
     let mut module = binaryen::Module::read(&dest_wasm_file_content)
         .map_err(|_| anyhow::anyhow!("binaryen failed to read file content"))?;
     module.optimize(&codegen_config);
@@ -281,11 +480,11 @@ fn optimize_wasm(crate_metadata: &CrateMetadata) -> Result<OptimizationResult> {
     let mut optimized_wasm_file = File::create(optimized.as_os_str())?;
     optimized_wasm_file.write_all(&optimized_wasm)?;
 
-    let original_size = metadata(&crate_metadata.dest_wasm)?.len() as f64 / 1000.0;
-    let optimized_size = metadata(&optimized)?.len() as f64 / 1000.0;
-
     // overwrite existing destination wasm file with the optimised version
     std::fs::rename(&optimized, &crate_metadata.dest_wasm)?;
+    write_symbol_map(crate_metadata)?;
+    let optimized_size = metadata(&crate_metadata.dest_wasm)?.len() as f64 / 1000.0;
+
     Ok(OptimizationResult {
         original_size,
         optimized_size,
@@ -300,32 +499,153 @@ fn execute(
     verbosity: Option<Verbosity>,
     optimize_contract: bool,
     build_artifact: BuildArtifacts,
+    build_settings: &BuildSettingsOverrides,
+    build_options: &BuildOptions,
+    unstable_flags: UnstableFlags,
+) -> Result<BuildResult> {
+    let crate_metadata = collect_crate_metadata(manifest_path, build_settings)?;
+    execute_build(
+        crate_metadata,
+        verbosity,
+        optimize_contract,
+        build_artifact,
+        build_options,
+        unstable_flags,
+    )
+}
+
+/// Builds the ink! contracts of the workspace, only the given `packages` if any.
+///
+/// The contracts share the target directory, their artifacts are written to
+/// `{target_dir}/{package_name}`.
+fn execute_workspace(
+    manifest_path: &ManifestPath,
+    packages: &[String],
+    verbosity: Option<Verbosity>,
+    build_artifact: BuildArtifacts,
+    build_settings: &BuildSettingsOverrides,
+    build_options: &BuildOptions,
+    unstable_flags: UnstableFlags,
+) -> Result<WorkspaceBuildResult> {
+    let contracts = crate_metadata::workspace_contracts(manifest_path, packages)?;
+    let mut results = Vec::new();
+    for (index, contract) in contracts.iter().enumerate() {
+        progress!(
+            "{} {} ({}/{})",
+            "Building contract".bright_green().bold(),
+            contract.name.bold(),
+            index + 1,
+            contracts.len()
+        );
+        let manifest_path = ManifestPath::new(&contract.manifest_path)?;
+        let mut crate_metadata = collect_crate_metadata(&manifest_path, build_settings)?;
+        crate_metadata.use_artifact_subdirectory();
+        let result = execute_build(
+            crate_metadata,
+            verbosity,
+            true,
+            build_artifact,
+            build_options,
+            unstable_flags.clone(),
+        )
+        .context(format!("Building contract '{}' failed", contract.name))?;
+        results.push((contract.name.clone(), result));
+    }
+    Ok(WorkspaceBuildResult { contracts: results })
+}
+
+/// Collects the metadata of the contract, with the build settings of the manifest overridden by
+/// the flags.
+fn collect_crate_metadata(
+    manifest_path: &ManifestPath,
+    build_settings: &BuildSettingsOverrides,
+) -> Result<CrateMetadata> {
+    let mut crate_metadata = CrateMetadata::collect(manifest_path)?;
+    build_settings.apply(&mut crate_metadata.build_settings);
+    if crate_metadata.build_settings.debug {
+        crate_metadata.use_debug_target_directory();
+    }
+    Ok(crate_metadata)
+}
+
+/// Executes build of the smart-contract which produces a wasm binary that is ready for deploying.
+///
+/// Uses the supplied `CrateMetadata`, with the build settings already applied.
+fn execute_build(
+    crate_metadata: CrateMetadata,
+    verbosity: Option<Verbosity>,
+    optimize_contract: bool,
+    build_artifact: BuildArtifacts,
+    build_options: &BuildOptions,
     unstable_flags: UnstableFlags,
 ) -> Result<BuildResult> {
     if build_artifact == BuildArtifacts::CodeOnly || build_artifact == BuildArtifacts::CheckOnly {
-        let crate_metadata = CrateMetadata::collect(manifest_path)?;
         let (maybe_dest_wasm, maybe_optimization_result) = execute_with_crate_metadata(
             &crate_metadata,
             verbosity,
             optimize_contract,
             build_artifact,
+            build_options,
             unstable_flags,
         )?;
+        let code_hash = match maybe_dest_wasm {
+            Some(ref dest_wasm) => Some(blake2_hash(&std::fs::read(dest_wasm)?)),
+            None => None,
+        };
         let res = BuildResult {
             dest_wasm: maybe_dest_wasm,
             dest_metadata: None,
             dest_bundle: None,
-            target_directory: crate_metadata.target_directory,
+            target_directory: crate_metadata.artifact_directory,
             optimization_result: maybe_optimization_result,
+            code_hash,
             build_artifact,
         };
         return Ok(res);
     }
 
-    let res = super::metadata::execute(&manifest_path, verbosity, build_artifact, unstable_flags)?;
+    let res = super::metadata::execute(
+        crate_metadata,
+        verbosity,
+        build_artifact,
+        build_options,
+        unstable_flags,
+    )?;
     Ok(res)
 }
 
+/// The build step of the Wasm in the build cache.
+const WASM_STEP: &str = "wasm";
+
+/// The result of building, post processing and optimizing the Wasm, stored in the build cache.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedWasm {
+    code_hash: CodeHash,
+    optimization_result: OptimizationResult,
+}
+
+/// Returns the optimization result of the cached build, if the Wasm and the symbol map of that
+/// build are still in place.
+fn cached_wasm(cache: &BuildCache, crate_metadata: &CrateMetadata) -> Option<OptimizationResult> {
+    let cached: CachedWasm = cache.get(WASM_STEP)?;
+    let wasm = std::fs::read(&crate_metadata.dest_wasm).ok()?;
+    if blake2_hash(&wasm) != cached.code_hash || !crate_metadata.symbol_map_path().exists() {
+        return None;
+    }
+    Some(cached.optimization_result)
+}
+
+/// Prints a build step which was skipped, since its result was taken from the build cache.
+pub(crate) fn print_cached_step(step: usize, build_artifact: BuildArtifacts, name: &str) {
+    progress!(
+        " {} {} {}",
+        format!("[{}/{}]", step, build_artifact.steps()).bold(),
+        name.bright_green().bold(),
+        "(cached)".bold()
+    );
+}
+
 /// Executes build of the smart-contract which produces a Wasm binary that is ready for deploying.
 ///
 /// It does so by invoking `cargo build` and then post processing the final binary.
@@ -340,29 +660,86 @@ pub(crate) fn execute_with_crate_metadata(
     verbosity: Option<Verbosity>,
     optimize_contract: bool,
     build_artifact: BuildArtifacts,
+    build_options: &BuildOptions,
     unstable_flags: UnstableFlags,
 ) -> Result<(Option<PathBuf>, Option<OptimizationResult>)> {
-    println!(
+    // only the optimized Wasm is cached, `check` does not produce any artifacts
+    let cache = if optimize_contract {
+        Some(BuildCache::new(
+            crate_metadata,
+            build_options,
+            &unstable_flags,
+        )?)
+    } else {
+        None
+    };
+    if let Some(optimization_result) = cache
+        .as_ref()
+        .and_then(|cache| cached_wasm(cache, crate_metadata))
+    {
+        print_cached_step(1, build_artifact, "Building cargo project");
+        print_cached_step(2, build_artifact, "Post processing wasm file");
+        if crate_metadata.build_settings.skip_optimization {
+            print_cached_step(3, build_artifact, "Skipping wasm optimization");
+        } else {
+            print_cached_step(3, build_artifact, "Optimizing wasm file");
+        }
+        ensure_maximum_code_size(crate_metadata, build_options, &optimization_result)?;
+        return Ok((
+            Some(crate_metadata.dest_wasm.clone()),
+            Some(optimization_result),
+        ));
+    }
+
+    progress!(
         " {} {}",
         format!("[1/{}]", build_artifact.steps()).bold(),
         "Building cargo project".bright_green().bold()
     );
-    build_cargo_project(&crate_metadata, build_artifact, verbosity, unstable_flags)?;
-    println!(
+    build_cargo_project(
+        &crate_metadata,
+        build_artifact,
+        verbosity,
+        build_options.cargo_flags,
+        unstable_flags,
+    )?;
+    progress!(
         " {} {}",
         format!("[2/{}]", build_artifact.steps()).bold(),
         "Post processing wasm file".bright_green().bold()
     );
-    post_process_wasm(&crate_metadata)?;
+    post_process_wasm(&crate_metadata, build_options.skip_validation)?;
     if !optimize_contract {
         return Ok((None, None));
     }
-    println!(
-        " {} {}",
-        format!("[3/{}]", build_artifact.steps()).bold(),
-        "Optimizing wasm file".bright_green().bold()
-    );
-    let optimization_result = optimize_wasm(&crate_metadata)?;
+    let optimization_result = if crate_metadata.build_settings.skip_optimization {
+        progress!(
+            " {} {}",
+            format!("[3/{}]", build_artifact.steps()).bold(),
+            "Skipping wasm optimization".bright_green().bold()
+        );
+        write_symbol_map(crate_metadata)?;
+        let size = metadata(&crate_metadata.dest_wasm)?.len() as f64 / 1000.0;
+        OptimizationResult {
+            original_size: size,
+            optimized_size: size,
+        }
+    } else {
+        progress!(
+            " {} {}",
+            format!("[3/{}]", build_artifact.steps()).bold(),
+            "Optimizing wasm file".bright_green().bold()
+        );
+        optimize_wasm(crate_metadata)?
+    };
+    if let Some(cache) = cache {
+        let cached = CachedWasm {
+            code_hash: blake2_hash(&std::fs::read(&crate_metadata.dest_wasm)?),
+            optimization_result: optimization_result.clone(),
+        };
+        cache.put(WASM_STEP, cached)?;
+    }
+    ensure_maximum_code_size(crate_metadata, build_options, &optimization_result)?;
     Ok((
         Some(crate_metadata.dest_wasm.clone()),
         Some(optimization_result),
@@ -372,7 +749,110 @@ pub(crate) fn execute_with_crate_metadata(
 #[cfg(feature = "test-ci-only")]
 #[cfg(test)]
 mod tests {
-    use crate::{cmd, util::tests::with_tmp_dir, BuildArtifacts, ManifestPath, UnstableFlags};
+    use crate::{
+        cmd, util::tests::with_tmp_dir, BuildArtifacts, BuildOptions, BuildSettingsOverrides,
+        ManifestPath, UnstableFlags,
+    };
+    use parity_wasm::{
+        builder,
+        elements::{CustomSection, External, Module, NameSection, Section},
+    };
+
+    fn module_with_memory_import(maximum: Option<u32>) -> Module {
+        builder::module()
+            .import()
+            .module("env")
+            .field("memory")
+            .external()
+            .memory(1, maximum)
+            .build()
+            .build()
+    }
+
+    fn memory_maximum(module: &Module) -> Option<u32> {
+        module
+            .import_section()
+            .and_then(|section| {
+                section
+                    .entries()
+                    .iter()
+                    .find_map(|entry| match entry.external() {
+                        External::Memory(mem_ty) => Some(mem_ty.limits().maximum()),
+                        _ => None,
+                    })
+            })
+            .expect("memory import must exist")
+    }
+
+    #[test]
+    fn memory_maximum_is_set_to_the_configured_pages() {
+        let mut module = module_with_memory_import(None);
+
+        super::ensure_maximum_memory_pages(&mut module, 32).expect("must be within limit");
+
+        assert_eq!(memory_maximum(&module), Some(32));
+    }
+
+    #[test]
+    fn memory_maximum_beyond_the_configured_pages_fails() {
+        let mut module = module_with_memory_import(Some(17));
+
+        let res = super::ensure_maximum_memory_pages(&mut module, 16);
+
+        assert!(res.is_err(), "17 pages must exceed the maximum of 16");
+    }
+
+    fn custom_sections(module: &Module) -> Vec<String> {
+        module
+            .sections()
+            .iter()
+            .filter_map(|section| match section {
+                Section::Custom(custom) => Some(custom.name().to_string()),
+                Section::Name(_) => Some("name".to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn debug_builds_keep_the_producers_section() {
+        let module = Module::new(vec![
+            Section::Custom(CustomSection::new("producers".into(), Vec::new())),
+            Section::Custom(CustomSection::new("target_features".into(), Vec::new())),
+            Section::Name(NameSection::new(None, None, None)),
+        ]);
+
+        let mut release = module.clone();
+        super::strip_custom_sections(&mut release, false);
+        let mut debug = module;
+        super::strip_custom_sections(&mut debug, true);
+
+        assert_eq!(custom_sections(&release), vec!["name"]);
+        assert_eq!(custom_sections(&debug), vec!["producers", "name"]);
+
+        super::strip_name_section(&mut release, false);
+        super::strip_name_section(&mut debug, true);
+        assert!(custom_sections(&release).is_empty());
+        assert_eq!(custom_sections(&debug), vec!["producers", "name"]);
+    }
+
+    #[test]
+    fn optimization_passes_turn_off_a_skip_optimization_of_the_manifest() {
+        use structopt::StructOpt;
+
+        let build = super::BuildCommand::from_iter(&["build", "--optimization-passes", "z"]);
+        let mut settings = crate::crate_metadata::BuildSettings {
+            skip_optimization: true,
+            ..Default::default()
+        };
+        build.build_settings_overrides().apply(&mut settings);
+
+        assert!(!settings.skip_optimization);
+        assert_eq!(
+            settings.optimization_passes,
+            crate::crate_metadata::OptimizationPasses::Z
+        );
+    }
 
     #[test]
     fn build_template() {
@@ -385,6 +865,8 @@ mod tests {
                 None,
                 true,
                 BuildArtifacts::All,
+                &BuildSettingsOverrides::default(),
+                &BuildOptions::default(),
                 UnstableFlags::default(),
             )
             .expect("build failed");
@@ -413,6 +895,8 @@ mod tests {
                 None,
                 true,
                 BuildArtifacts::CheckOnly,
+                &BuildSettingsOverrides::default(),
+                &BuildOptions::default(),
                 UnstableFlags::default(),
             )
             .expect("build failed");
@@ -0,0 +1,449 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::TryFrom;
+
+use anyhow::{Context, Result};
+use codec::Decode;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sp_core::{crypto::AccountId32, Bytes};
+use structopt::StructOpt;
+use subxt::{
+    balances::Balances, contracts::*, system::System, ClientBuilder, DefaultNodeRuntime, RawEvent,
+    Signer,
+};
+
+use crate::{transcode::InkProject, util, ExtrinsicOpts, HexData};
+
+/// Calls a message of an instantiated smart contract.
+///
+/// With `--dry-run` the call is executed via the `contracts_call` RPC of the node instead, which
+/// reports the consumed gas and the output without submitting an extrinsic.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "call")]
+pub struct CallCommand {
+    #[structopt(flatten)]
+    extrinsic_opts: ExtrinsicOpts,
+    /// The account of the instantiated contract, as SS58 address or hex
+    #[structopt(long)]
+    contract: AccountId32,
+    /// Value transferred to the contract with the call
+    #[structopt(name = "value", long, default_value = "0")]
+    value: u128,
+    /// Maximum amount of gas to be used for this command
+    #[structopt(name = "gas", long, default_value = "500000000")]
+    gas_limit: u64,
+    /// Hex encoded data to call a contract message
+    #[structopt(long, required_unless = "message")]
+    data: Option<HexData>,
+    /// Name of the message to call, the call data is encoded using the contract metadata
+    #[structopt(long, conflicts_with = "data")]
+    message: Option<String>,
+    /// Arguments of the message, given as JSON values or plain strings
+    #[structopt(long, requires = "message")]
+    args: Vec<String>,
+    /// Execute the call via RPC without submitting an extrinsic
+    #[structopt(long)]
+    dry_run: bool,
+    /// Set the gas limit to the gas consumed by a dry run plus `--gas-margin`
+    #[structopt(long, conflicts_with = "gas")]
+    estimate_gas: bool,
+    /// Margin in percent added to the gas estimate of `--estimate-gas`
+    #[structopt(long, default_value = "10")]
+    gas_margin: u64,
+}
+
+impl CallCommand {
+    pub fn exec(&self) -> Result<String> {
+        let (data, project) = match (&self.data, &self.message) {
+            // contract events are displayed undecoded if no metadata is available
+            (Some(data), _) => (data.clone(), InkProject::load_default().ok()),
+            (None, Some(message)) => {
+                let project = InkProject::load_default()?;
+                let data = HexData(project.encode_message(message, &self.args)?);
+                (data, Some(project))
+            }
+            (None, None) => unreachable!("either --data or --message is required"),
+        };
+
+        if self.dry_run {
+            let result = dry_run_call(
+                &self.extrinsic_opts,
+                &self.contract,
+                self.value,
+                self.gas_limit,
+                &data,
+            )?;
+            if util::output_json() {
+                let json = result.to_json(self.message.as_deref(), project.as_ref());
+                return Ok(serde_json::to_string_pretty(&json)?);
+            }
+            return Ok(result.display(self.message.as_deref(), project.as_ref()));
+        }
+
+        let gas_limit = if self.estimate_gas {
+            let result = dry_run_call(
+                &self.extrinsic_opts,
+                &self.contract,
+                self.value,
+                self.gas_limit,
+                &data,
+            )?;
+            if result.did_revert() {
+                anyhow::bail!(
+                    "The dry run reverted, not submitting the call:\n\t{}",
+                    result.display(self.message.as_deref(), project.as_ref())
+                );
+            }
+            let gas_limit = gas_with_margin(result.gas_consumed, self.gas_margin);
+            progress!(
+                "{} {} (consumed {} + {}%)",
+                "Estimated gas limit:".bold(),
+                gas_limit,
+                result.gas_consumed,
+                self.gas_margin
+            );
+            gas_limit
+        } else {
+            self.gas_limit
+        };
+
+        let events = execute_call(
+            &self.extrinsic_opts,
+            &self.contract,
+            self.value,
+            gas_limit,
+            data,
+        )?;
+        if util::output_json() {
+            let json = serde_json::json!({ "events": events_json(&events, project.as_ref()) });
+            return Ok(serde_json::to_string_pretty(&json)?);
+        }
+        Ok(format!(
+            "Events:\n\t{}",
+            display_events(&events, project.as_ref())
+        ))
+    }
+}
+
+/// Call a message of the contract instantiated at the supplied account.
+/// Returns the events emitted by the extrinsic if successful.
+///
+/// Creates an extrinsic with the `Contracts::call` Call, submits via RPC, then waits for it to
+/// be included in a block.
+pub(crate) fn execute_call(
+    extrinsic_opts: &ExtrinsicOpts,
+    contract: &<DefaultNodeRuntime as System>::AccountId,
+    value: <DefaultNodeRuntime as Balances>::Balance,
+    gas_limit: u64,
+    data: HexData,
+) -> Result<Vec<RawEvent>> {
+    async_std::task::block_on(async move {
+        let cli = ClientBuilder::<DefaultNodeRuntime>::new()
+            .set_url(&extrinsic_opts.url.to_string())
+            .build()
+            .await?;
+        let signer = extrinsic_opts.signer()?;
+        let dest = contract.clone().into();
+
+        let result = cli
+            .call_and_watch(&signer, &dest, value, gas_limit, &data.0)
+            .await?;
+
+        Ok(result.events)
+    })
+}
+
+/// Parameters of the `contracts_call` RPC.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CallRequest {
+    origin: AccountId32,
+    dest: AccountId32,
+    /// The value as hex, since JSON numbers cannot represent every `u128`.
+    value: String,
+    gas_limit: u64,
+    input_data: Bytes,
+}
+
+/// Result of the `contracts_call` RPC.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum RpcContractExecResult {
+    Success {
+        flags: u32,
+        data: Bytes,
+        gas_consumed: u64,
+    },
+    Error(()),
+}
+
+/// Result of a successful dry run of a call.
+pub(crate) struct DryRunResult {
+    pub flags: u32,
+    pub data: Vec<u8>,
+    pub gas_consumed: u64,
+}
+
+impl DryRunResult {
+    /// Returns `true` if the contract reverted the execution.
+    pub fn did_revert(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    /// Formats the result, the output is decoded if the message and metadata are known.
+    pub fn display(&self, message: Option<&str>, project: Option<&InkProject>) -> String {
+        let output = match self.decode_output(message, project) {
+            Some(decoded) => decoded.to_string(),
+            None => format!("0x{}", hex::encode(&self.data)),
+        };
+        format!(
+            "{} {}\n\t{} {}\n\t{} {}",
+            "Gas consumed:".bold(),
+            self.gas_consumed,
+            if self.did_revert() {
+                "Reverted:".bright_red().bold()
+            } else {
+                "Output:".bold()
+            },
+            output,
+            "Flags:".bold(),
+            self.flags
+        )
+    }
+
+    /// Returns the result as JSON, the output is decoded if the message and metadata are known.
+    pub fn to_json(&self, message: Option<&str>, project: Option<&InkProject>) -> Value {
+        serde_json::json!({
+            "gasConsumed": self.gas_consumed,
+            "reverted": self.did_revert(),
+            "output": self
+                .decode_output(message, project)
+                .unwrap_or_else(|| format!("0x{}", hex::encode(&self.data)).into()),
+            "flags": self.flags,
+        })
+    }
+
+    /// Decodes the output, if the message and metadata are known.
+    fn decode_output(&self, message: Option<&str>, project: Option<&InkProject>) -> Option<Value> {
+        match (message, project) {
+            (Some(message), Some(project)) if !self.did_revert() => project
+                .decode_return(message, &self.data)
+                .map_err(|err| log::warn!("Failed to decode return value: {:?}", err))
+                .ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Executes the call via the `contracts_call` RPC, without submitting an extrinsic.
+///
+/// The origin of the call is the account of the signer.
+pub(crate) fn dry_run_call(
+    extrinsic_opts: &ExtrinsicOpts,
+    contract: &AccountId32,
+    value: u128,
+    gas_limit: u64,
+    data: &HexData,
+) -> Result<DryRunResult> {
+    async_std::task::block_on(async move {
+        let cli = jsonrpsee::ws_client(extrinsic_opts.url.as_str()).await?;
+        let signer = extrinsic_opts.signer()?;
+        let request = CallRequest {
+            origin: Signer::<DefaultNodeRuntime>::account_id(&signer).clone(),
+            dest: contract.clone(),
+            value: format!("0x{:x}", value),
+            gas_limit,
+            input_data: Bytes(data.0.clone()),
+        };
+        let params = jsonrpsee::common::Params::Array(vec![serde_json::to_value(request)?]);
+        let result: RpcContractExecResult = cli
+            .request("contracts_call", params)
+            .await
+            .context("The contracts_call RPC failed")?;
+
+        match result {
+            RpcContractExecResult::Success {
+                flags,
+                data,
+                gas_consumed,
+            } => Ok(DryRunResult {
+                flags,
+                data: data.0,
+                gas_consumed,
+            }),
+            RpcContractExecResult::Error(()) => {
+                anyhow::bail!("The contract execution failed during the dry run")
+            }
+        }
+    })
+}
+
+/// Adds the margin in percent to the consumed gas, saturating at the maximum gas limit.
+fn gas_with_margin(gas_consumed: u64, margin: u64) -> u64 {
+    let gas_limit = u128::from(gas_consumed) * (100 + u128::from(margin)) / 100;
+    u64::try_from(gas_limit).unwrap_or(u64::MAX)
+}
+
+/// Formats the events emitted by an extrinsic, one per line.
+///
+/// Events emitted by the contract are decoded using its metadata, if it is available. All other
+/// events are displayed hex encoded.
+pub(crate) fn display_events(events: &[RawEvent], project: Option<&InkProject>) -> String {
+    events
+        .iter()
+        .map(|event| match decode_event_data(event, project) {
+            Some(decoded) => format!("{}::{} {}", event.module, event.variant, decoded),
+            None => format!(
+                "{}::{} 0x{}",
+                event.module,
+                event.variant,
+                hex::encode(&event.data)
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\t")
+}
+
+/// Returns the events emitted by an extrinsic as JSON array.
+///
+/// The data of the events is decoded like in [`display_events`].
+pub(crate) fn events_json(events: &[RawEvent], project: Option<&InkProject>) -> Value {
+    events
+        .iter()
+        .map(|event| {
+            serde_json::json!({
+                "module": event.module,
+                "variant": event.variant,
+                "data": decode_event_data(event, project)
+                    .unwrap_or_else(|| format!("0x{}", hex::encode(&event.data)).into()),
+            })
+        })
+        .collect()
+}
+
+/// Decodes the data of the events emitted by the contract, if the metadata is available.
+fn decode_event_data(event: &RawEvent, project: Option<&InkProject>) -> Option<Value> {
+    match project {
+        Some(project) if is_contract_event(event) => decode_contract_event(project, &event.data)
+            .map_err(|err| log::warn!("Failed to decode contract event: {:?}", err))
+            .ok(),
+        _ => None,
+    }
+}
+
+/// Returns `true` for the event carrying the data of an event emitted by a contract.
+fn is_contract_event(event: &RawEvent) -> bool {
+    event.module == "Contracts"
+        && (event.variant == "ContractExecution" || event.variant == "ContractEmitted")
+}
+
+/// Decodes the data of a contract event, consisting of the contract account and the SCALE
+/// encoded event emitted by the contract.
+fn decode_contract_event(project: &InkProject, data: &[u8]) -> Result<Value> {
+    let (_contract, event) =
+        <(<DefaultNodeRuntime as System>::AccountId, Vec<u8>)>::decode(&mut &data[..])?;
+    project.decode_event(&event)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use super::{gas_with_margin, CallCommand};
+    use crate::{
+        cmd::{deploy::execute_deploy, instantiate::execute_instantiate},
+        util::tests::with_tmp_dir,
+        ExtrinsicOpts, HexData,
+    };
+    use assert_matches::assert_matches;
+    use structopt::{clap, StructOpt};
+
+    const CONTRACT: &str = r#"
+(module
+    (func (export "call"))
+    (func (export "deploy"))
+)
+"#;
+
+    #[test]
+    #[ignore] // depends on a local substrate node running
+    fn call_contract() {
+        with_tmp_dir(|path| {
+            let wasm = wabt::wat2wasm(CONTRACT).expect("invalid wabt");
+
+            let wasm_path = path.join("test.wasm");
+            let mut file = fs::File::create(&wasm_path).unwrap();
+            let _ = file.write_all(&wasm);
+
+            let url = url::Url::parse("ws://localhost:9944").unwrap();
+            let extrinsic_opts = ExtrinsicOpts {
+                url,
+                suri: "//Alice".into(),
+                password: None,
+            };
+            let code_hash =
+                execute_deploy(&extrinsic_opts, Some(&wasm_path)).expect("Deploy should succeed");
+
+            let gas_limit = 500_000_000;
+            let (contract, _) = execute_instantiate(
+                &extrinsic_opts,
+                100000000000000,
+                gas_limit,
+                code_hash,
+                HexData::default(),
+            )
+            .expect("Instantiate should succeed");
+
+            let result =
+                super::execute_call(&extrinsic_opts, &contract, 0, gas_limit, HexData::default());
+
+            assert_matches!(result, Ok(_));
+            Ok(())
+        })
+    }
+
+    fn parse_call(args: &[&str]) -> clap::Result<CallCommand> {
+        let required = [
+            "call",
+            "--suri",
+            "//Alice",
+            "--contract",
+            "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+            "--data",
+            "00",
+        ];
+        CallCommand::from_iter_safe(required.iter().chain(args))
+    }
+
+    #[test]
+    fn estimate_gas_conflicts_with_an_explicit_gas_limit() {
+        assert_matches!(parse_call(&["--estimate-gas"]), Ok(call) if call.estimate_gas);
+        assert_matches!(
+            parse_call(&["--estimate-gas", "--gas", "100"]).map_err(|err| err.kind),
+            Err(clap::ErrorKind::ArgumentConflict)
+        );
+    }
+
+    #[test]
+    fn gas_margin_saturates_at_the_maximum_gas_limit() {
+        assert_eq!(gas_with_margin(1000, 10), 1100);
+        assert_eq!(gas_with_margin(u64::MAX / 2, 100), u64::MAX - 1);
+        assert_eq!(gas_with_margin(u64::MAX, 10), u64::MAX);
+    }
+}
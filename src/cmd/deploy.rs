@@ -14,32 +14,122 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{fs, io::Read, path::PathBuf};
+use std::path::PathBuf;
 
-use anyhow::{Context, Result};
-use sp_core::H256;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use sp_core::{crypto::AccountId32, H256};
+use structopt::StructOpt;
 use subxt::{contracts::*, ClientBuilder, DefaultNodeRuntime};
 
-use crate::{crate_metadata, ExtrinsicOpts};
+use crate::{
+    crate_metadata::CrateMetadata,
+    transcode::InkProject,
+    util::{self, load_contract_code},
+    workspace::ManifestPath,
+    ExtrinsicOpts, HexData,
+};
 
-/// Load the wasm blob from the specified path.
+/// Uploads the smart contract code to the chain.
 ///
-/// Defaults to the target contract wasm in the current project, inferred via the crate metadata.
-fn load_contract_code(path: Option<&PathBuf>) -> Result<Vec<u8>> {
-    let contract_wasm_path = match path {
-        Some(path) => path.clone(),
-        None => {
-            let metadata = crate_metadata::CrateMetadata::collect(&Default::default())?;
-            metadata.dest_wasm
+/// With `--instantiate` the uploaded code is instantiated right away, by default the code and
+/// metadata are taken from the `<name>.contract` bundle of the current project then.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "deploy")]
+pub struct DeployCommand {
+    #[structopt(flatten)]
+    extrinsic_opts: ExtrinsicOpts,
+    /// Path to wasm contract code or a `.contract` bundle, defaults to `<name>.wasm` or with
+    /// `--instantiate` to `<name>.contract` in the artifact directory, e.g. `./target/ink`
+    #[structopt(parse(from_os_str))]
+    wasm_path: Option<PathBuf>,
+    /// Instantiate the contract after uploading its code
+    #[structopt(long)]
+    instantiate: bool,
+    /// Transfers an initial balance to the instantiated contract [default: 0]
+    #[structopt(name = "endowment", long, requires = "instantiate")]
+    endowment: Option<u128>,
+    /// Maximum amount of gas to be used for the instantiation [default: 500000000]
+    #[structopt(name = "gas", long, requires = "instantiate")]
+    gas_limit: Option<u64>,
+    /// Hex encoded data to call a contract constructor
+    #[structopt(long, requires = "instantiate")]
+    data: Option<HexData>,
+    /// Name of the constructor to call, the call data is encoded using the contract metadata
+    #[structopt(long, requires = "instantiate", conflicts_with = "data")]
+    constructor: Option<String>,
+    /// Arguments of the constructor, given as JSON values or plain strings
+    #[structopt(long, requires = "constructor")]
+    args: Vec<String>,
+}
+
+/// The gas limit of the instantiation if `--gas` is not given.
+const DEFAULT_GAS_LIMIT: u64 = 500_000_000;
+
+/// Result of uploading and instantiating a contract.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeployResult {
+    code_hash: H256,
+    contract: AccountId32,
+    /// The events of the instantiation, decoded like the ones of `instantiate`.
+    events: Value,
+}
+
+impl DeployCommand {
+    pub fn exec(&self) -> Result<String> {
+        if !self.instantiate {
+            let code_hash = execute_deploy(&self.extrinsic_opts, self.wasm_path.as_ref())?;
+            if util::output_json() {
+                let json = serde_json::json!({ "codeHash": code_hash });
+                return Ok(serde_json::to_string_pretty(&json)?);
+            }
+            return Ok(format!("Code hash: {:?}", code_hash));
         }
-    };
-    log::info!("Contract code path: {}", contract_wasm_path.display());
-    let mut data = Vec::new();
-    let mut file = fs::File::open(&contract_wasm_path)
-        .context(format!("Failed to open {}", contract_wasm_path.display()))?;
-    file.read_to_end(&mut data)?;
 
-    Ok(data)
+        let path = match self.wasm_path {
+            Some(ref path) => path.clone(),
+            None => {
+                let crate_metadata = CrateMetadata::collect(&ManifestPath::discover()?)?;
+                crate_metadata
+                    .artifact_directory
+                    .join(format!("{}.contract", crate_metadata.package_name))
+            }
+        };
+        let project = || {
+            if path.extension() == Some("contract".as_ref()) {
+                InkProject::load(&path)
+            } else {
+                InkProject::load_default()
+            }
+        };
+        // encode the call data before uploading, so invalid arguments are rejected early
+        let data = match (&self.data, &self.constructor) {
+            (Some(data), _) => data.clone(),
+            (None, Some(constructor)) => {
+                HexData(project()?.encode_constructor(constructor, &self.args)?)
+            }
+            (None, None) => anyhow::bail!("--instantiate requires --constructor or --data"),
+        };
+
+        let code_hash = execute_deploy(&self.extrinsic_opts, Some(&path))?;
+        let (contract, events) = super::execute_instantiate(
+            &self.extrinsic_opts,
+            self.endowment.unwrap_or(0),
+            self.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT),
+            code_hash,
+            data,
+        )?;
+        // contract events are displayed undecoded if no metadata is available
+        let project = project().ok();
+        let result = DeployResult {
+            code_hash,
+            contract,
+            events: super::events_json(&events, project.as_ref()),
+        };
+        Ok(serde_json::to_string_pretty(&result)?)
+    }
 }
 
 /// Put contract code to a smart contract enabled substrate chain.
@@ -76,8 +166,13 @@ pub(crate) fn execute_deploy(
 mod tests {
     use std::{fs, io::Write};
 
-    use crate::{cmd::deploy::execute_deploy, util::tests::with_tmp_dir, ExtrinsicOpts};
+    use crate::{
+        cmd::deploy::{execute_deploy, DeployCommand},
+        util::tests::with_tmp_dir,
+        ExtrinsicOpts,
+    };
     use assert_matches::assert_matches;
+    use structopt::{clap, StructOpt};
 
     const CONTRACT: &str = r#"
 (module
@@ -108,4 +203,18 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn instantiation_args_require_instantiate() {
+        let parse = |args: &[&str]| {
+            let required = ["deploy", "--suri", "//Alice"];
+            DeployCommand::from_iter_safe(required.iter().chain(args)).map_err(|err| err.kind)
+        };
+
+        assert_matches!(parse(&[]), Ok(_));
+        assert_matches!(parse(&["--instantiate", "--gas", "100"]), Ok(_));
+        for args in &[["--gas", "100"], ["--endowment", "100"]] {
+            assert_matches!(parse(args), Err(clap::ErrorKind::MissingRequiredArgument));
+        }
+    }
 }
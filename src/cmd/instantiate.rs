@@ -15,12 +15,14 @@
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
 use anyhow::Result;
-use subxt::{balances::Balances, contracts::*, system::System, ClientBuilder, DefaultNodeRuntime};
+use subxt::{
+    balances::Balances, contracts::*, system::System, ClientBuilder, DefaultNodeRuntime, RawEvent,
+};
 
 use crate::{ExtrinsicOpts, HexData};
 
 /// Instantiate a contract stored at the supplied code hash.
-/// Returns the account id of the instantiated contract if successful.
+/// Returns the account id of the instantiated contract and all emitted events if successful.
 ///
 /// Creates an extrinsic with the `Contracts::instantiate` Call, submits via RPC, then waits for
 /// the `ContractsEvent::Instantiated` event.
@@ -30,7 +32,7 @@ pub(crate) fn execute_instantiate(
     gas_limit: u64,
     code_hash: <DefaultNodeRuntime as System>::Hash,
     data: HexData,
-) -> Result<<DefaultNodeRuntime as System>::AccountId> {
+) -> Result<(<DefaultNodeRuntime as System>::AccountId, Vec<RawEvent>)> {
     async_std::task::block_on(async move {
         let cli = ClientBuilder::<DefaultNodeRuntime>::new()
             .set_url(&extrinsic_opts.url.to_string())
@@ -45,7 +47,7 @@ pub(crate) fn execute_instantiate(
             .instantiated()?
             .ok_or(anyhow::anyhow!("Failed to find Instantiated event"))?;
 
-        Ok(instantiated.contract)
+        Ok((instantiated.contract, events.events))
     })
 }
 
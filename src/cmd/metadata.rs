@@ -15,10 +15,11 @@
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
-    crate_metadata::CrateMetadata,
+    build_cache::BuildCache,
+    crate_metadata::{BuildSettings, CrateMetadata},
     util,
     workspace::{ManifestPath, Workspace},
-    BuildArtifacts, BuildResult, OptimizationResult, UnstableFlags, Verbosity,
+    BuildArtifacts, BuildOptions, BuildResult, OptimizationResult, UnstableFlags, Verbosity,
 };
 
 use anyhow::Result;
@@ -29,16 +30,21 @@ use contract_metadata::{
     SourceLanguage, SourceWasm, User,
 };
 use semver::Version;
+use serde_json::{Map, Value};
 use std::{fs, path::PathBuf};
 use url::Url;
 
 const METADATA_FILE: &str = "metadata.json";
 
+/// The build step of the ink! metadata in the build cache.
+const METADATA_STEP: &str = "metadata";
+
 /// Executes the metadata generation process
 struct GenerateMetadataCommand {
     crate_metadata: CrateMetadata,
     verbosity: Option<Verbosity>,
     build_artifact: BuildArtifacts,
+    build_options: BuildOptions,
     unstable_options: UnstableFlags,
 }
 
@@ -55,11 +61,11 @@ impl GenerateMetadataCommand {
     pub fn exec(&self) -> Result<BuildResult> {
         util::assert_channel()?;
 
-        let target_directory = self.crate_metadata.target_directory.clone();
-        let out_path_metadata = target_directory.join(METADATA_FILE);
+        let artifact_directory = self.crate_metadata.artifact_directory.clone();
+        let out_path_metadata = artifact_directory.join(METADATA_FILE);
 
         let fname_bundle = format!("{}.contract", self.crate_metadata.package_name);
-        let out_path_bundle = target_directory.join(fname_bundle);
+        let out_path_bundle = artifact_directory.join(fname_bundle);
 
         // build the extended contract project metadata
         let ExtendedMetadataResult {
@@ -69,49 +75,93 @@ impl GenerateMetadataCommand {
             user,
             optimization_result,
         } = self.extended_metadata()?;
+        let code_hash = source.hash().clone();
 
-        let generate_metadata = |manifest_path: &ManifestPath| -> Result<()> {
-            let mut current_progress = 4;
-            println!(
+        let mut current_progress = 4;
+        let cache = BuildCache::new(
+            &self.crate_metadata,
+            &self.build_options,
+            &self.unstable_options,
+        )?;
+        let ink_meta = match cache.get(METADATA_STEP) {
+            Some(ink_meta) => {
+                super::build::print_cached_step(
+                    current_progress,
+                    self.build_artifact,
+                    "Generating metadata",
+                );
+                ink_meta
+            }
+            None => {
+                progress!(
+                    " {} {}",
+                    format!("[{}/{}]", current_progress, self.build_artifact.steps()).bold(),
+                    "Generating metadata".bright_green().bold()
+                );
+                let ink_meta = self.generate_ink_metadata()?;
+                cache.put(METADATA_STEP, &ink_meta)?;
+                ink_meta
+            }
+        };
+
+        let metadata = ContractMetadata::new(source, contract, user, ink_meta);
+        {
+            let mut metadata = metadata.clone();
+            metadata.remove_source_wasm_attribute();
+            let contents = serde_json::to_string_pretty(&metadata)?;
+            fs::write(&out_path_metadata, contents)?;
+            current_progress += 1;
+        }
+
+        if self.build_artifact == BuildArtifacts::All {
+            progress!(
                 " {} {}",
                 format!("[{}/{}]", current_progress, self.build_artifact.steps()).bold(),
-                "Generating metadata".bright_green().bold()
+                "Generating bundle".bright_green().bold()
             );
+            let contents = serde_json::to_string(&metadata)?;
+            fs::write(&out_path_bundle, contents)?;
+        }
+
+        let dest_bundle = if self.build_artifact == BuildArtifacts::All {
+            Some(out_path_bundle)
+        } else {
+            None
+        };
+        Ok(BuildResult {
+            dest_metadata: Some(out_path_metadata),
+            dest_wasm,
+            dest_bundle,
+            optimization_result,
+            code_hash: Some(code_hash),
+            target_directory: artifact_directory,
+            build_artifact: self.build_artifact,
+        })
+    }
+
+    /// Generates the ink! metadata of the contract by running the `metadata-gen` package.
+    fn generate_ink_metadata(&self) -> Result<Map<String, Value>> {
+        let target_directory = &self.crate_metadata.target_directory;
+        let mut ink_meta = Map::new();
+        let mut generate_metadata = |manifest_path: &ManifestPath| -> Result<()> {
             let target_dir_arg = format!("--target-dir={}", target_directory.to_string_lossy());
+            let manifest_path_arg = manifest_path.cargo_arg();
+            let mut args = vec![
+                "--package",
+                "metadata-gen",
+                &manifest_path_arg,
+                &target_dir_arg,
+                "--release",
+            ];
+            args.extend(self.build_options.cargo_flags.metadata_args());
             let stdout = util::invoke_cargo(
                 "run",
-                &[
-                    "--package",
-                    "metadata-gen",
-                    &manifest_path.cargo_arg(),
-                    &target_dir_arg,
-                    "--release",
-                ],
+                &args,
                 self.crate_metadata.manifest_path.directory(),
                 self.verbosity,
+                vec![],
             )?;
-
-            let ink_meta: serde_json::Map<String, serde_json::Value> =
-                serde_json::from_slice(&stdout)?;
-            let metadata = ContractMetadata::new(source, contract, user, ink_meta);
-            {
-                let mut metadata = metadata.clone();
-                metadata.remove_source_wasm_attribute();
-                let contents = serde_json::to_string_pretty(&metadata)?;
-                fs::write(&out_path_metadata, contents)?;
-                current_progress += 1;
-            }
-
-            if self.build_artifact == BuildArtifacts::All {
-                println!(
-                    " {} {}",
-                    format!("[{}/{}]", current_progress, self.build_artifact.steps()).bold(),
-                    "Generating bundle".bright_green().bold()
-                );
-                let contents = serde_json::to_string(&metadata)?;
-                fs::write(&out_path_bundle, contents)?;
-            }
-
+            ink_meta = serde_json::from_slice(&stdout)?;
             Ok(())
         };
 
@@ -129,22 +179,10 @@ impl GenerateMetadataCommand {
                 Ok(())
             })?
             .with_metadata_gen_package()?
+            .keep_temp(self.unstable_options.keep_temp_workspace)
             .using_temp(generate_metadata)?;
         }
-
-        let dest_bundle = if self.build_artifact == BuildArtifacts::All {
-            Some(out_path_bundle)
-        } else {
-            None
-        };
-        Ok(BuildResult {
-            dest_metadata: Some(out_path_metadata),
-            dest_wasm,
-            dest_bundle,
-            optimization_result,
-            target_directory,
-            build_artifact: self.build_artifact,
-        })
+        Ok(ink_meta)
     }
 
     /// Generate the extended contract project metadata
@@ -180,7 +218,8 @@ impl GenerateMetadataCommand {
             } else {
                 None
             };
-            Source::new(maybe_wasm, hash, lang, compiler)
+            let build_info = build_info(&self.crate_metadata.build_settings);
+            Source::new(maybe_wasm, hash, lang, compiler).with_build_info(build_info)
         };
 
         // Required contract fields
@@ -230,23 +269,64 @@ impl GenerateMetadataCommand {
     ///
     /// Return a tuple of `(dest_wasm, hash, optimization_result)`.
     fn wasm_hash(&self) -> Result<(PathBuf, CodeHash, OptimizationResult)> {
-        let (maybe_dest_wasm, maybe_optimization_res) = super::build::execute_with_crate_metadata(
+        wasm_hash(
             &self.crate_metadata,
             self.verbosity,
-            true, // for the hash we always use the optimized version of the contract
             self.build_artifact,
+            &self.build_options,
             self.unstable_options.clone(),
-        )?;
-
-        let wasm = fs::read(&self.crate_metadata.dest_wasm)?;
-        let dest_wasm = maybe_dest_wasm.expect("dest wasm must exist");
-        let optimization_res = maybe_optimization_res.expect("optimization result must exist");
-        Ok((dest_wasm, blake2_hash(wasm.as_slice()), optimization_res))
+        )
     }
 }
 
+/// Compile the contract and then hash the resulting Wasm.
+///
+/// Return a tuple of `(dest_wasm, hash, optimization_result)`.
+pub(crate) fn wasm_hash(
+    crate_metadata: &CrateMetadata,
+    verbosity: Option<Verbosity>,
+    build_artifact: BuildArtifacts,
+    build_options: &BuildOptions,
+    unstable_options: UnstableFlags,
+) -> Result<(PathBuf, CodeHash, OptimizationResult)> {
+    let (maybe_dest_wasm, maybe_optimization_res) = super::build::execute_with_crate_metadata(
+        crate_metadata,
+        verbosity,
+        true, // for the hash we always use the optimized version of the contract
+        build_artifact,
+        build_options,
+        unstable_options,
+    )?;
+
+    let wasm = fs::read(&crate_metadata.dest_wasm)?;
+    let dest_wasm = maybe_dest_wasm.expect("dest wasm must exist");
+    let optimization_res = maybe_optimization_res.expect("optimization result must exist");
+    Ok((dest_wasm, blake2_hash(wasm.as_slice()), optimization_res))
+}
+
+/// Returns the settings the Wasm was built with, so deployers know what the Wasm expects.
+pub(crate) fn build_info(build_settings: &BuildSettings) -> Map<String, Value> {
+    let mut build_info = Map::new();
+    build_info.insert(
+        "maxMemoryPages".into(),
+        build_settings.max_memory_pages.into(),
+    );
+    build_info.insert("stackSize".into(), build_settings.stack_size.into());
+    build_info.insert("linkArgs".into(), build_settings.link_args.clone().into());
+    build_info.insert(
+        "optimizationPasses".into(),
+        build_settings.optimization_passes.to_string().into(),
+    );
+    build_info.insert(
+        "skipOptimization".into(),
+        build_settings.skip_optimization.into(),
+    );
+    build_info.insert("debug".into(), build_settings.debug.into());
+    build_info
+}
+
 /// Returns the blake2 hash of the submitted slice.
-fn blake2_hash(code: &[u8]) -> CodeHash {
+pub(crate) fn blake2_hash(code: &[u8]) -> CodeHash {
     let mut output = [0u8; 32];
     let mut blake2 = blake2::VarBlake2b::new_keyed(&[], 32);
     blake2.update(code);
@@ -258,16 +338,17 @@ fn blake2_hash(code: &[u8]) -> CodeHash {
 ///
 /// It does so by generating and invoking a temporary workspace member.
 pub(crate) fn execute(
-    manifest_path: &ManifestPath,
+    crate_metadata: CrateMetadata,
     verbosity: Option<Verbosity>,
     build_artifact: BuildArtifacts,
+    build_options: &BuildOptions,
     unstable_options: UnstableFlags,
 ) -> Result<BuildResult> {
-    let crate_metadata = CrateMetadata::collect(manifest_path)?;
     let res = GenerateMetadataCommand {
         crate_metadata,
         verbosity,
         build_artifact,
+        build_options: build_options.clone(),
         unstable_options,
     }
     .exec()?;
@@ -280,7 +361,7 @@ mod tests {
     use crate::cmd::metadata::blake2_hash;
     use crate::{
         cmd, crate_metadata::CrateMetadata, util::tests::with_tmp_dir, BuildArtifacts,
-        ManifestPath, UnstableFlags,
+        BuildOptions, ManifestPath, UnstableFlags,
     };
     use contract_metadata::*;
     use serde_json::{Map, Value};
@@ -375,9 +456,10 @@ mod tests {
 
             let crate_metadata = CrateMetadata::collect(&test_manifest.manifest_path)?;
             let dest_bundle = cmd::metadata::execute(
-                &test_manifest.manifest_path,
+                CrateMetadata::collect(&test_manifest.manifest_path)?,
                 None,
                 BuildArtifacts::All,
+                &BuildOptions::default(),
                 UnstableFlags::default(),
             )?
             .dest_bundle
@@ -16,12 +16,30 @@
 
 pub mod build;
 #[cfg(feature = "extrinsics")]
+mod call;
+#[cfg(feature = "extrinsics")]
 mod deploy;
 #[cfg(feature = "extrinsics")]
 mod instantiate;
 pub mod metadata;
 pub mod new;
+pub mod run;
+pub mod size;
+pub mod symbolize;
+pub mod test;
+pub mod verify;
 
-pub(crate) use self::build::{BuildCommand, CheckCommand};
+pub(crate) use self::{
+    build::{BuildCommand, CheckCommand},
+    run::RunCommand,
+    size::SizeCommand,
+    symbolize::SymbolizeCommand,
+    test::TestCommand,
+    verify::VerifyCommand,
+};
 #[cfg(feature = "extrinsics")]
-pub(crate) use self::{deploy::execute_deploy, instantiate::execute_instantiate};
+pub(crate) use self::{
+    call::{display_events, events_json, CallCommand},
+    deploy::DeployCommand,
+    instantiate::execute_instantiate,
+};
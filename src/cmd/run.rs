@@ -0,0 +1,169 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::PathBuf;
+
+use crate::{
+    cmd::symbolize::SymbolMap,
+    crate_metadata::CrateMetadata,
+    sandbox::{Balance, ExecResult, FunctionNames, Sandbox},
+    util,
+    workspace::ManifestPath,
+    HexData,
+};
+use anyhow::Result;
+use colored::Colorize;
+use structopt::StructOpt;
+
+/// Executes a constructor and messages of a built contract in a local sandbox.
+///
+/// The contract runs against a throwaway in-memory state, no node is required.
+///
+/// In profiling mode the executed instructions, host function calls and storage accesses are
+/// reported for every execution.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "run")]
+pub struct RunCommand {
+    /// Path to wasm contract code, defaults to `./target/ink/<name>.wasm`.
+    ///
+    /// With `--profile` this optimized Wasm is measured, as it is deployed. Its functions are
+    /// named by its `name` section if it has one, like the Wasm of `build --debug` in
+    /// `./target/ink/debug`, otherwise by the `<name>.symbols.json` of the build next to it.
+    #[structopt(parse(from_os_str))]
+    wasm_path: Option<PathBuf>,
+    /// Hex encoded data to call a contract constructor
+    #[structopt(long)]
+    deploy: HexData,
+    /// Hex encoded data to call a contract message, may be specified multiple times
+    #[structopt(long = "call")]
+    calls: Vec<HexData>,
+    /// Value transferred to the contract with every execution
+    #[structopt(long, default_value = "0")]
+    value: Balance,
+    /// Count the executed instructions and report the hot functions of every execution
+    #[structopt(long)]
+    profile: bool,
+    /// Number of hot functions to report in profiling mode
+    #[structopt(long, default_value = "10")]
+    top: usize,
+}
+
+impl RunCommand {
+    pub fn exec(&self) -> Result<String> {
+        let wasm_path = match self.wasm_path {
+            Some(ref path) => path.clone(),
+            None => CrateMetadata::collect(&ManifestPath::discover()?)?.dest_wasm,
+        };
+        let code = util::load_contract_code(Some(&wasm_path))?;
+        let mut sandbox = if self.profile {
+            Sandbox::new_profiled(&code)?
+        } else {
+            Sandbox::new(&code)?
+        };
+        if self.profile && sandbox.function_names().is_empty() {
+            // the `name` section is stripped from release builds, but recorded in the symbol map
+            let symbols = wasm_path.with_extension("symbols.json");
+            if symbols.exists() {
+                sandbox.set_function_names(SymbolMap::load(&symbols)?.into());
+            } else {
+                progress!(
+                    "{} {}",
+                    "warning:".yellow().bold(),
+                    format!(
+                        "the Wasm has no `name` section and there is no {}, functions are \
+                         reported by index",
+                        symbols.display()
+                    )
+                    .bold()
+                );
+            }
+        }
+        let steps = self.calls.len() + 1;
+
+        print_step(1, steps, "Executing constructor");
+        let result = sandbox.deploy(&self.deploy.0, self.value)?;
+        self.print_exec_result(&result, sandbox.function_names());
+        if result.did_revert() {
+            anyhow::bail!("The constructor reverted")
+        }
+
+        for (i, call) in self.calls.iter().enumerate() {
+            print_step(i + 2, steps, &format!("Executing message #{}", i + 1));
+            let result = sandbox.call(&call.0, self.value)?;
+            self.print_exec_result(&result, sandbox.function_names());
+        }
+
+        Ok(format!(
+            "\nExecuted the constructor and {} messages in the sandbox",
+            self.calls.len()
+        ))
+    }
+
+    fn print_exec_result(&self, result: &ExecResult, function_names: &FunctionNames) {
+        for message in &result.debug_messages {
+            progress!("   {} {}", "debug:".bold(), message);
+        }
+        for event in &result.events {
+            progress!(
+                "   {} topics: 0x{}, data: 0x{}",
+                "event:".bold(),
+                hex::encode(&event.topics),
+                hex::encode(&event.data)
+            );
+        }
+        let output = format!("0x{}", hex::encode(&result.data));
+        if result.did_revert() {
+            progress!("   {} {}", "reverted:".bright_red().bold(), output);
+        } else {
+            progress!("   {} {}", "output:".bold(), output);
+        }
+
+        if !self.profile {
+            return;
+        }
+        let profile = &result.profile;
+        progress!(
+            "   {} {}",
+            "instructions:".bold(),
+            profile.total_instructions()
+        );
+        progress!(
+            "   {} {} reads, {} writes",
+            "storage:".bold(),
+            profile.storage_reads,
+            profile.storage_writes
+        );
+        let host_calls = profile
+            .host_calls
+            .iter()
+            .map(|(name, count)| format!("{} {}", name, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        progress!("   {} {}", "host calls:".bold(), host_calls);
+        progress!("   {}", "hot functions:".bold());
+        for (name, instructions) in profile.hot_functions(function_names, self.top) {
+            progress!("   {:>12}  {}", instructions, name);
+        }
+    }
+}
+
+fn print_step(current: usize, steps: usize, name: &str) {
+    progress!(
+        " {} {}",
+        format!("[{}/{}]", current, steps).bold(),
+        name.bright_green().bold()
+    );
+}
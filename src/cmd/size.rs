@@ -0,0 +1,425 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{cmd::symbolize::SymbolMap, crate_metadata::CrateMetadata, workspace::ManifestPath};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use parity_wasm::elements::{ImportCountType, Module};
+use structopt::StructOpt;
+
+/// The crate of the data segments.
+const DATA: &str = "<data>";
+
+/// The crate of the functions without a name.
+const UNKNOWN: &str = "<unknown>";
+
+/// The crate of the bytes which are neither part of a function nor a data segment.
+const OTHER: &str = "<other sections>";
+
+/// Reports which functions, data segments and crates make up the size of the contract's Wasm.
+///
+/// The function names are taken from the `name` section of a debug build, or from the
+/// `<name>.symbols.json` written alongside the Wasm by `cargo contract build`.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "size")]
+pub struct SizeCommand {
+    /// Path to the Wasm to analyze, defaults to `./target/ink/<name>.wasm`
+    #[structopt(parse(from_os_str))]
+    wasm_path: Option<PathBuf>,
+    /// Path to the Cargo.toml of the contract, whose last build is analyzed
+    #[structopt(long, parse(from_os_str), conflicts_with = "wasm-path")]
+    manifest_path: Option<PathBuf>,
+    /// Analyze the last debug build, i.e. `./target/ink/debug/<name>.wasm`
+    #[structopt(long, conflicts_with = "wasm-path")]
+    debug: bool,
+    /// Path to the symbol map, defaults to the `<name>.symbols.json` next to the Wasm
+    #[structopt(long, parse(from_os_str))]
+    symbols: Option<PathBuf>,
+    /// Compare against an older Wasm, e.g. the one of the base branch, using the
+    /// `<name>.symbols.json` next to it
+    #[structopt(long, parse(from_os_str))]
+    diff: Option<PathBuf>,
+    /// Number of functions and data segments to report
+    #[structopt(long, default_value = "10")]
+    top: usize,
+}
+
+impl SizeCommand {
+    pub fn exec(&self) -> Result<String> {
+        let wasm_path = match self.wasm_path {
+            Some(ref wasm_path) => wasm_path.clone(),
+            None => {
+                let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+                let mut crate_metadata = CrateMetadata::collect(&manifest_path)?;
+                if self.debug {
+                    crate_metadata.use_debug_target_directory();
+                }
+                crate_metadata.dest_wasm
+            }
+        };
+        let report = SizeReport::load(&wasm_path, self.symbols.as_deref())?;
+
+        match self.diff {
+            Some(ref old_wasm_path) => {
+                let old = SizeReport::load(old_wasm_path, None)?;
+                Ok(report.display_diff(&old, self.top))
+            }
+            None => Ok(report.display(self.top)),
+        }
+    }
+}
+
+/// A function or data segment of the Wasm.
+#[derive(Debug, PartialEq)]
+struct Item {
+    name: String,
+    krate: String,
+    size: usize,
+}
+
+/// The sizes of the functions and data segments of a Wasm.
+#[derive(Debug)]
+pub(crate) struct SizeReport {
+    /// The size of the whole Wasm in bytes.
+    total: usize,
+    /// The functions and data segments, the largest first.
+    items: Vec<Item>,
+}
+
+impl SizeReport {
+    /// Analyzes the Wasm at the given path.
+    ///
+    /// The function names are taken from the `name` section if present, otherwise from the
+    /// given symbol map or the `<name>.symbols.json` next to the Wasm if it exists.
+    pub(crate) fn load(wasm_path: &Path, symbols: Option<&Path>) -> Result<Self> {
+        let wasm =
+            fs::read(wasm_path).context(format!("Failed to read {}", wasm_path.display()))?;
+        let module: Module = parity_wasm::deserialize_buffer(&wasm)
+            .context(format!("Failed to parse {}", wasm_path.display()))?;
+
+        let mut names = SymbolMap::from_module(&module);
+        if names.is_empty() {
+            let default_symbols = wasm_path.with_extension("symbols.json");
+            match symbols {
+                Some(symbols) => names = SymbolMap::load(symbols)?,
+                None if default_symbols.exists() => names = SymbolMap::load(&default_symbols)?,
+                None => (),
+            }
+        }
+        SizeReport::new(&module, wasm.len(), &names)
+    }
+
+    fn new(module: &Module, total: usize, names: &SymbolMap) -> Result<Self> {
+        let imported_functions = module.import_count(ImportCountType::Function);
+        let mut items = Vec::new();
+        if let Some(code) = module.code_section() {
+            for (index, body) in code.bodies().iter().enumerate() {
+                let index = (imported_functions + index) as u32;
+                let size = parity_wasm::serialize(body.clone())?.len();
+                let item = match names.get(index) {
+                    Some(name) => Item {
+                        name: name.to_string(),
+                        krate: crate_name(name).to_string(),
+                        size,
+                    },
+                    None => Item {
+                        name: format!("func[{}]", index),
+                        krate: UNKNOWN.to_string(),
+                        size,
+                    },
+                };
+                items.push(item);
+            }
+        }
+        if let Some(data) = module.data_section() {
+            for (index, segment) in data.entries().iter().enumerate() {
+                items.push(Item {
+                    name: format!("data[{}]", index),
+                    krate: DATA.to_string(),
+                    size: segment.value().len(),
+                });
+            }
+        }
+        items.sort_by_key(|item| std::cmp::Reverse(item.size));
+        Ok(SizeReport { total, items })
+    }
+
+    /// Returns the size of every crate, including the data segments and the remaining bytes of
+    /// the Wasm.
+    fn crates(&self) -> BTreeMap<&str, usize> {
+        let mut crates = BTreeMap::new();
+        for item in &self.items {
+            *crates.entry(item.krate.as_str()).or_insert(0) += item.size;
+        }
+        let attributed: usize = self.items.iter().map(|item| item.size).sum();
+        crates.insert(OTHER, self.total.saturating_sub(attributed));
+        crates
+    }
+
+    /// Returns the size of the functions and data segments by name.
+    ///
+    /// The sizes of functions with the same name, e.g. of different monomorphizations, are summed
+    /// up.
+    fn items_by_name(&self) -> BTreeMap<&str, usize> {
+        let mut items = BTreeMap::new();
+        for item in &self.items {
+            *items.entry(item.name.as_str()).or_insert(0) += item.size;
+        }
+        items
+    }
+
+    pub(crate) fn display(&self, top: usize) -> String {
+        let mut out = format!("\nTotal size: {} bytes\n", self.total.to_string().bold());
+
+        out.push_str(&format!(
+            "\n{}\n",
+            format!("{:>10} {:>7}  Function", "Size", "%").bold()
+        ));
+        for item in self.items.iter().take(top) {
+            out.push_str(&format!(
+                "{:>10} {:>7}  {}\n",
+                item.size,
+                percent(item.size, self.total),
+                item.name
+            ));
+        }
+
+        out.push_str(&format!(
+            "\n{}\n",
+            format!("{:>10} {:>7}  Crate", "Size", "%").bold()
+        ));
+        let mut crates = self.crates().into_iter().collect::<Vec<_>>();
+        crates.sort_by(|(_, a), (_, b)| b.cmp(a));
+        for (krate, size) in crates {
+            out.push_str(&format!(
+                "{:>10} {:>7}  {}\n",
+                size,
+                percent(size, self.total),
+                krate
+            ));
+        }
+        out
+    }
+
+    /// Displays the changes in size compared to the `old` Wasm.
+    ///
+    /// Functions are matched by name, since their indices change between builds.
+    fn display_diff(&self, old: &SizeReport, top: usize) -> String {
+        let mut out = format!(
+            "\nTotal size: {} -> {} bytes ({})\n",
+            old.total,
+            self.total,
+            delta(old.total, self.total).bold()
+        );
+
+        out.push_str(&format!(
+            "\n{}\n",
+            format!("{:>10} {:>10} {:>10}  Function", "Delta", "Old", "New").bold()
+        ));
+        for (name, old_size, new_size) in diff(&old.items_by_name(), &self.items_by_name())
+            .into_iter()
+            .take(top)
+        {
+            out.push_str(&format!(
+                "{:>10} {:>10} {:>10}  {}\n",
+                delta(old_size, new_size),
+                old_size,
+                new_size,
+                name
+            ));
+        }
+
+        out.push_str(&format!(
+            "\n{}\n",
+            format!("{:>10} {:>10} {:>10}  Crate", "Delta", "Old", "New").bold()
+        ));
+        for (krate, old_size, new_size) in diff(&old.crates(), &self.crates()) {
+            out.push_str(&format!(
+                "{:>10} {:>10} {:>10}  {}\n",
+                delta(old_size, new_size),
+                old_size,
+                new_size,
+                krate
+            ));
+        }
+        out
+    }
+}
+
+/// Returns the name, old and new size of all entries whose size changed, the largest change
+/// first.
+fn diff<'a>(
+    old: &BTreeMap<&'a str, usize>,
+    new: &BTreeMap<&'a str, usize>,
+) -> Vec<(&'a str, usize, usize)> {
+    let mut names = old.keys().chain(new.keys()).copied().collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+    let mut changes = names
+        .into_iter()
+        .map(|name| {
+            let old_size = old.get(name).copied().unwrap_or(0);
+            let new_size = new.get(name).copied().unwrap_or(0);
+            (name, old_size, new_size)
+        })
+        .filter(|(_, old_size, new_size)| old_size != new_size)
+        .collect::<Vec<_>>();
+    changes.sort_by_key(|(_, old_size, new_size)| {
+        std::cmp::Reverse((*new_size as i64 - *old_size as i64).abs())
+    });
+    changes
+}
+
+/// Returns the crate of a demangled function name, e.g. `ink_env` for
+/// `<ink_env::engine::on_chain::EnvInstance as ink_env::backend::Env>::emit_event`.
+///
+/// Trait implementations for generic types are attributed to the crate of the trait.
+fn crate_name(function: &str) -> &str {
+    let path = match function.strip_prefix('<') {
+        Some(qualified) => match qualified.find(" as ") {
+            Some(as_trait) if !qualified[..as_trait].contains("::") => {
+                &qualified[as_trait + " as ".len()..]
+            }
+            _ => qualified,
+        },
+        None => function,
+    };
+    let path = path.trim_start_matches(&['&', '*', '['][..]);
+    let path = path.strip_prefix("mut ").unwrap_or(path);
+    let end = path
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(path.len());
+    &path[..end]
+}
+
+fn percent(size: usize, total: usize) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{:.1}%", size as f64 * 100.0 / total as f64)
+}
+
+fn delta(old: usize, new: usize) -> String {
+    format!("{:+}", new as i64 - old as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(wat: &str) -> SizeReport {
+        let wasm = wabt::Wat2Wasm::new()
+            .write_debug_names(true)
+            .convert(wat)
+            .expect("invalid wat");
+        let wasm: &[u8] = wasm.as_ref();
+        let module: Module = parity_wasm::deserialize_buffer(wasm).expect("invalid wasm");
+        let names = SymbolMap::from_module(&module);
+        SizeReport::new(&module, wasm.len(), &names).expect("analysis failed")
+    }
+
+    const OLD: &str = r#"
+        (module
+            (import "env" "memory" (memory 1 16))
+            (func $flipper::flip (export "call") (drop (i32.const 1)))
+            (func $deploy (export "deploy"))
+            (data (i32.const 0) "flipper")
+        )
+    "#;
+
+    const NEW: &str = r#"
+        (module
+            (import "env" "memory" (memory 1 16))
+            (func $flipper::flip (export "call")
+                (drop (i32.const 1))
+                (drop (i32.const 2))
+                (call $ink_env::emit))
+            (func $ink_env::emit)
+            (func $deploy (export "deploy"))
+            (data (i32.const 0) "flipper")
+        )
+    "#;
+
+    #[test]
+    fn size_is_attributed_to_functions_data_and_crates() {
+        let report = report(NEW);
+
+        let items = report
+            .items
+            .iter()
+            .map(|item| (item.name.as_str(), item.krate.as_str(), item.size))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![
+                ("flipper::flip", "flipper", 11),
+                ("data[0]", DATA, 7),
+                ("ink_env::emit", "ink_env", 3),
+                ("deploy", "deploy", 3),
+            ]
+        );
+        let crates = report.crates();
+        assert_eq!(crates[OTHER], report.total - 24);
+        assert_eq!(crates.values().sum::<usize>(), report.total);
+    }
+
+    #[test]
+    fn diff_reports_the_changed_functions_and_crates() {
+        let (old, new) = (report(OLD), report(NEW));
+
+        let functions = diff(&old.items_by_name(), &new.items_by_name());
+        let crates = diff(&old.crates(), &new.crates());
+
+        assert_eq!(
+            functions,
+            vec![("flipper::flip", 6, 11), ("ink_env::emit", 0, 3)]
+        );
+        // the `name` section grew as well
+        assert_eq!(
+            crates,
+            vec![
+                (OTHER, old.total - 16, new.total - 24),
+                ("flipper", 6, 11),
+                ("ink_env", 0, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn crate_names_are_taken_from_function_paths() {
+        assert_eq!(crate_name("flipper::flipper::Flipper::flip"), "flipper");
+        assert_eq!(
+            crate_name("<ink_env::engine::on_chain::EnvInstance as ink_env::backend::Env>::hash"),
+            "ink_env"
+        );
+        assert_eq!(
+            crate_name("<&mut T as core::fmt::Write>::write_str"),
+            "core"
+        );
+        assert_eq!(
+            crate_name("core::ptr::drop_in_place<alloc::string::String>"),
+            "core"
+        );
+        assert_eq!(crate_name("deploy"), "deploy");
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{crate_metadata::CrateMetadata, sandbox::FunctionNames, workspace::ManifestPath};
+use anyhow::{Context, Result};
+use parity_wasm::elements::Module;
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// The text preceding function indices in trap messages, e.g. `func[42]` or the
+/// `<wasm function 42>` of wasmtime backtraces.
+const INDEX_PREFIXES: [&str; 2] = ["func[", "wasm function "];
+
+/// Maps function indices of the stripped contract Wasm back to the names of the functions.
+///
+/// The names are looked up in the `<name>.symbols.json` written by `cargo contract build`.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "symbolize")]
+pub struct SymbolizeCommand {
+    /// A function index, or a trap message containing function indices like `func[42]`
+    input: String,
+    /// Path to the Cargo.toml of the contract, whose last build is used
+    #[structopt(long, parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+    /// Path to the symbol map, instead of the one of the contract's last build
+    #[structopt(long, parse(from_os_str), conflicts_with = "manifest-path")]
+    symbols: Option<PathBuf>,
+}
+
+impl SymbolizeCommand {
+    pub fn exec(&self) -> Result<String> {
+        let symbols = match self.symbols {
+            Some(ref symbols) => symbols.clone(),
+            None => {
+                let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+                CrateMetadata::collect(&manifest_path)?.symbol_map_path()
+            }
+        };
+        let symbol_map = SymbolMap::load(&symbols)?;
+
+        if let Ok(index) = self.input.trim().parse::<u32>() {
+            return symbol_map
+                .get(index)
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("Function {} is not in the symbol map", index));
+        }
+        symbol_map
+            .symbolize(&self.input)
+            .ok_or_else(|| anyhow::anyhow!("No function indices found in '{}'", self.input.trim()))
+    }
+}
+
+/// The demangled function names of the contract Wasm by function index.
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub(crate) struct SymbolMap(BTreeMap<u32, String>);
+
+impl SymbolMap {
+    /// Collects the function names from the `name` section of the module.
+    pub fn from_module(module: &Module) -> Self {
+        let names = FunctionNames::from_module(module)
+            .iter()
+            .map(|(index, name)| (index, name.to_string()))
+            .collect();
+        SymbolMap(names)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read(path).context(format!(
+            "Failed to read the symbol map {}, build the contract first",
+            path.display()
+        ))?;
+        serde_json::from_slice(&json).context(format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Returns `true` if no function names are known.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the name of the function with the given index.
+    pub fn get(&self, index: u32) -> Option<&str> {
+        self.0.get(&index).map(String::as_str)
+    }
+
+    /// Appends the function names to all function indices of the trap message.
+    ///
+    /// Returns `None` if the message does not contain any function index.
+    pub fn symbolize(&self, message: &str) -> Option<String> {
+        let indices = function_indices(message);
+        if indices.is_empty() {
+            return None;
+        }
+        let mut symbolized = String::new();
+        let mut last = 0;
+        for (end, index) in indices {
+            symbolized.push_str(&message[last..end]);
+            if let Some(name) = self.get(index) {
+                symbolized.push_str(&format!(" ({})", name));
+            }
+            last = end;
+        }
+        symbolized.push_str(&message[last..]);
+        Some(symbolized)
+    }
+}
+
+impl From<SymbolMap> for FunctionNames {
+    fn from(symbol_map: SymbolMap) -> Self {
+        symbol_map.0.into_iter().collect()
+    }
+}
+
+/// Returns the function indices found in the message, with the offset of the end of each
+/// occurrence, ordered by offset.
+fn function_indices(message: &str) -> Vec<(usize, u32)> {
+    let mut indices = INDEX_PREFIXES
+        .iter()
+        .flat_map(|prefix| {
+            message.match_indices(prefix).filter_map(move |(start, _)| {
+                let digits_start = start + prefix.len();
+                let digits = message[digits_start..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(message.len() - digits_start);
+                let index = message[digits_start..digits_start + digits].parse().ok()?;
+                let mut end = digits_start + digits;
+                // include the closing bracket of `func[42]` or `<wasm function 42>`
+                if message[end..].starts_with(']') || message[end..].starts_with('>') {
+                    end += 1;
+                }
+                Some((end, index))
+            })
+        })
+        .collect::<Vec<_>>();
+    indices.sort_unstable();
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::with_tmp_dir;
+
+    fn symbol_map() -> SymbolMap {
+        let names = vec![
+            (3, "flipper::flipper::Flipper::flip".to_string()),
+            (7, "ink_env::engine::on_chain::ext::panic".to_string()),
+        ];
+        SymbolMap(names.into_iter().collect())
+    }
+
+    #[test]
+    fn function_indices_of_trap_messages_are_symbolized() {
+        let message = "wasm trap: unreachable\n  \
+                       0: 0x1a2b - <unknown>!<wasm function 7>\n  \
+                       1: 0x0c3d - <unknown>!<wasm function 3>\n  \
+                       at func[12]";
+
+        let symbolized = symbol_map().symbolize(message);
+
+        assert_eq!(
+            symbolized.as_deref(),
+            Some(
+                "wasm trap: unreachable\n  \
+                 0: 0x1a2b - <unknown>!<wasm function 7> (ink_env::engine::on_chain::ext::panic)\n  \
+                 1: 0x0c3d - <unknown>!<wasm function 3> (flipper::flipper::Flipper::flip)\n  \
+                 at func[12]"
+            )
+        );
+        assert_eq!(symbol_map().symbolize("ContractTrapped"), None);
+    }
+
+    #[test]
+    fn symbol_maps_name_the_profiled_functions() {
+        let names = FunctionNames::from(symbol_map());
+
+        assert_eq!(names.name(3), "flipper::flipper::Flipper::flip");
+        assert_eq!(names.name(12), "func[12]");
+    }
+
+    #[test]
+    fn symbol_map_roundtrips_through_json() {
+        with_tmp_dir(|path| {
+            let path = path.join("flipper.symbols.json");
+            symbol_map().save(&path)?;
+
+            let json = fs::read_to_string(&path)?;
+            assert!(json.contains(r#""3": "flipper::flipper::Flipper::flip""#));
+            assert_eq!(SymbolMap::load(&path)?, symbol_map());
+            Ok(())
+        })
+    }
+}
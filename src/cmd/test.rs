@@ -0,0 +1,262 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{convert::TryFrom, path::PathBuf, process::Stdio};
+
+use crate::{util, workspace::ManifestPath, Verbosity, VerbosityFlags};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use structopt::StructOpt;
+
+/// The number of steps required to run the tests, used as output on the cli.
+const TEST_STEPS: usize = 2;
+
+/// Executes the off-chain tests of the smart contract.
+///
+/// It does so by invoking `cargo test` with the `std` feature enabled, which makes ink! use its
+/// off-chain environment.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "test")]
+pub struct TestCommand {
+    /// Path to the Cargo.toml of the contract to test
+    #[structopt(long, parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+    /// Space or comma separated list of additional features to activate
+    #[structopt(long, use_delimiter = true)]
+    features: Vec<String>,
+    #[structopt(flatten)]
+    verbosity: VerbosityFlags,
+    /// Only run tests containing one of these strings in their names
+    #[structopt(name = "TESTNAME")]
+    filters: Vec<String>,
+}
+
+impl TestCommand {
+    pub fn exec(&self) -> Result<TestResult> {
+        let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+        let verbosity: Option<Verbosity> = TryFrom::<&VerbosityFlags>::try_from(&self.verbosity)?;
+        execute(&manifest_path, &self.features, &self.filters, verbosity)
+    }
+}
+
+/// The outcome of a single test.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// Result of running the contract's off-chain tests.
+pub struct TestResult {
+    /// The name and outcome of every test that was run, in the order they were reported.
+    pub tests: Vec<(String, TestOutcome)>,
+}
+
+impl TestResult {
+    /// Collects the outcome of every test from the output of the libtest harness.
+    fn from_harness_output(stdout: &str) -> Self {
+        let tests = stdout
+            .lines()
+            .filter_map(|line| {
+                let line = line.strip_prefix("test ")?;
+                let separator = line.rfind(" ... ")?;
+                let (name, outcome) = (&line[..separator], &line[separator + 5..]);
+                let outcome = match outcome {
+                    "ok" => TestOutcome::Passed,
+                    "FAILED" => TestOutcome::Failed,
+                    ignored if ignored.starts_with("ignored") => TestOutcome::Ignored,
+                    _ => return None,
+                };
+                Some((name.to_string(), outcome))
+            })
+            .collect();
+        TestResult { tests }
+    }
+
+    /// Returns the number of tests with the given outcome.
+    pub fn count(&self, outcome: TestOutcome) -> usize {
+        self.tests.iter().filter(|(_, o)| *o == outcome).count()
+    }
+
+    pub fn display(&self) -> String {
+        format!(
+            "\nTest result: {} passed; {} failed; {} ignored",
+            self.count(TestOutcome::Passed).to_string().bold(),
+            self.count(TestOutcome::Failed).to_string().bold(),
+            self.count(TestOutcome::Ignored).to_string().bold(),
+        )
+    }
+}
+
+/// Returns the `failures:` section of the libtest harness output, containing the captured output
+/// of the failed tests.
+fn failure_details(stdout: &str) -> Option<&str> {
+    let start = stdout.find("\nfailures:\n")?;
+    let end = stdout[start..]
+        .find("\ntest result:")
+        .map_or(stdout.len(), |end| start + end);
+    Some(stdout[start..end].trim())
+}
+
+/// Returns the `--features` arg with the `std` feature and the supplied `features`.
+///
+/// Commas are already split by the argument parser, the features are split on whitespace like
+/// cargo does.
+fn features_arg(features: &[String]) -> String {
+    let features = std::iter::once("std")
+        .chain(
+            features
+                .iter()
+                .flat_map(|features| features.split_whitespace()),
+        )
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("--features={}", features)
+}
+
+/// Builds and runs the off-chain tests of the contract at the given manifest path.
+///
+/// The `std` feature is always enabled, the supplied `features` are activated in addition.
+/// Test `filters` are forwarded to the test harness.
+pub(crate) fn execute(
+    manifest_path: &ManifestPath,
+    features: &[String],
+    filters: &[String],
+    verbosity: Option<Verbosity>,
+) -> Result<TestResult> {
+    let features_arg = features_arg(features);
+
+    progress!(
+        " {} {}",
+        format!("[1/{}]", TEST_STEPS).bold(),
+        "Building tests".bright_green().bold()
+    );
+    util::invoke_cargo(
+        "test",
+        [features_arg.as_str(), "--no-run"],
+        manifest_path.directory(),
+        verbosity,
+        vec![],
+    )?;
+
+    progress!(
+        " {} {}",
+        format!("[2/{}]", TEST_STEPS).bold(),
+        "Running tests".bright_green().bold()
+    );
+    let mut args = vec![features_arg.as_str(), "--"];
+    args.extend(filters.iter().map(String::as_str));
+    // the harness output must not be terse, otherwise the test names cannot be collected
+    let mut cmd = util::cargo_cmd("test", &args, manifest_path.directory(), None);
+    log::info!("invoking cargo: {:?}", cmd);
+    let output = cmd
+        .stderr(Stdio::inherit())
+        .output()
+        .context(format!("Error executing `{:?}`", cmd))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let result = TestResult::from_harness_output(&stdout);
+
+    if !matches!(verbosity, Some(Verbosity::Quiet)) {
+        for (name, outcome) in &result.tests {
+            let outcome = match outcome {
+                TestOutcome::Passed => "ok".bright_green(),
+                TestOutcome::Failed => "FAILED".bright_red(),
+                TestOutcome::Ignored => "ignored".yellow(),
+            };
+            progress!("   {} ... {}", name, outcome.bold());
+        }
+    }
+
+    if !output.status.success() {
+        let failed = result.count(TestOutcome::Failed);
+        // e.g. a crashed test harness, the cause is printed to the inherited stderr
+        if failed == 0 {
+            anyhow::bail!(
+                "`cargo test` failed with {}, see the output above",
+                output.status
+            );
+        }
+        if let Some(details) = failure_details(&stdout) {
+            progress!("\n{}", details);
+        }
+        anyhow::bail!("{} of {} tests failed", failed, result.tests.len());
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const HARNESS_OUTPUT: &str = r#"
+running 4 tests
+test flipper::tests::default_works ... ok
+test flipper::tests::it_works ... FAILED
+test flipper::tests::slow ... ignored
+test flipper::tests::slow_with_reason ... ignored, requires a node
+
+failures:
+
+---- flipper::tests::it_works stdout ----
+thread 'flipper::tests::it_works' panicked at 'assertion failed: flipper.get()'
+
+failures:
+    flipper::tests::it_works
+
+test result: FAILED. 1 passed; 1 failed; 2 ignored; 0 measured; 0 filtered out
+"#;
+
+    #[test]
+    fn features_are_separated_by_space_or_comma() {
+        let command = TestCommand::from_iter(&["test", "--features", "foo bar,baz"]);
+
+        assert_eq!(
+            features_arg(&command.features),
+            "--features=std,foo,bar,baz"
+        );
+    }
+
+    #[test]
+    fn collects_test_outcomes_from_harness_output() {
+        let result = TestResult::from_harness_output(HARNESS_OUTPUT);
+
+        assert_eq!(
+            result.tests,
+            vec![
+                ("flipper::tests::default_works".into(), TestOutcome::Passed),
+                ("flipper::tests::it_works".into(), TestOutcome::Failed),
+                ("flipper::tests::slow".into(), TestOutcome::Ignored),
+                (
+                    "flipper::tests::slow_with_reason".into(),
+                    TestOutcome::Ignored
+                ),
+            ]
+        );
+        assert_eq!(result.count(TestOutcome::Ignored), 2);
+    }
+
+    #[test]
+    fn extracts_failure_details_from_harness_output() {
+        let details = failure_details(HARNESS_OUTPUT).expect("failures section exists");
+
+        assert!(details.starts_with("failures:"));
+        assert!(details.contains("panicked at 'assertion failed: flipper.get()'"));
+        assert!(details.ends_with("flipper::tests::it_works"));
+        assert_eq!(failure_details("test result: ok."), None);
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{convert::TryFrom, fs, path::PathBuf};
+
+use crate::{
+    cmd::metadata,
+    crate_metadata::{BuildSettings, CrateMetadata},
+    workspace::ManifestPath,
+    BuildArtifacts, BuildOptions, UnstableFlags, UnstableOptions, Verbosity, VerbosityFlags,
+};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use contract_metadata::{
+    CodeHash, Compiler, ContractMetadata, Language, Source, SourceCompiler, SourceLanguage,
+};
+use semver::Version;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use structopt::StructOpt;
+
+/// Verifies that a `.contract` bundle was built from the local sources.
+///
+/// The contract is rebuilt in `target/ink/verify` with the local toolchain and the hash of the
+/// resulting Wasm is compared against the code hash recorded in the bundle.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "verify")]
+pub struct VerifyCommand {
+    /// Path to the `.contract` bundle to verify against
+    #[structopt(parse(from_os_str))]
+    bundle: PathBuf,
+    /// Path to the Cargo.toml of the contract to build
+    #[structopt(long, parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+    #[structopt(flatten)]
+    verbosity: VerbosityFlags,
+    #[structopt(flatten)]
+    unstable_options: UnstableOptions,
+}
+
+impl VerifyCommand {
+    pub fn exec(&self) -> Result<VerificationResult> {
+        let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+        let unstable_flags: UnstableFlags =
+            TryFrom::<&UnstableOptions>::try_from(&self.unstable_options)?;
+        let verbosity: Option<Verbosity> = TryFrom::<&VerbosityFlags>::try_from(&self.verbosity)?;
+        execute(&self.bundle, &manifest_path, verbosity, unstable_flags)
+    }
+}
+
+/// A source attribute of the bundle which differs from the local build.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    /// Name of the differing attribute.
+    pub attribute: &'static str,
+    /// Value recorded in the bundle.
+    pub bundle: String,
+    /// Value of the local build.
+    pub local: String,
+}
+
+/// Result of a successful verification.
+pub struct VerificationResult {
+    /// The code hash of the bundle and the local build.
+    pub hash: CodeHash,
+    /// Path to the `.contract` bundle.
+    pub bundle: PathBuf,
+}
+
+impl VerificationResult {
+    pub fn display(&self) -> String {
+        format!(
+            "\nThe code hash {} of the local build matches {}",
+            format_hash(&self.hash).bold(),
+            self.bundle.display().to_string().bold()
+        )
+    }
+}
+
+/// Compares the source attributes of the bundle against the ones of the local build.
+///
+/// Returns the mismatching attributes, in the order hash, language and compiler.
+fn compare_sources(bundle: &Source, local: &Source) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    if bundle.hash() != local.hash() {
+        mismatches.push(Mismatch {
+            attribute: "hash",
+            bundle: format_hash(bundle.hash()),
+            local: format_hash(local.hash()),
+        });
+    }
+    if bundle.language() != local.language() {
+        mismatches.push(Mismatch {
+            attribute: "language",
+            bundle: bundle.language().to_string(),
+            local: local.language().to_string(),
+        });
+    }
+    if bundle.compiler() != local.compiler() {
+        mismatches.push(Mismatch {
+            attribute: "compiler",
+            bundle: bundle.compiler().to_string(),
+            local: local.compiler().to_string(),
+        });
+    }
+    mismatches
+}
+
+fn format_hash(hash: &CodeHash) -> String {
+    format!("0x{}", hex::encode(hash.0))
+}
+
+/// Applies the settings recorded in the `source.buildInfo` of the bundle to the rebuild, the
+/// inverse of `metadata::build_info`.
+///
+/// Fails on settings unknown to this version of `cargo-contract`, since the bundle could not be
+/// reproduced without them.
+fn apply_build_info(
+    build_settings: &mut BuildSettings,
+    build_info: &Map<String, Value>,
+) -> Result<()> {
+    fn setting<T: DeserializeOwned>(key: &str, value: &Value) -> Result<T> {
+        serde_json::from_value(value.clone()).context(format!(
+            "Invalid {} {} in the buildInfo of the bundle",
+            key, value
+        ))
+    }
+    for (key, value) in build_info {
+        match key.as_str() {
+            "maxMemoryPages" => build_settings.max_memory_pages = setting(key, value)?,
+            "stackSize" => build_settings.stack_size = setting(key, value)?,
+            "linkArgs" => build_settings.link_args = setting(key, value)?,
+            "optimizationPasses" => build_settings.optimization_passes = setting(key, value)?,
+            "skipOptimization" => build_settings.skip_optimization = setting(key, value)?,
+            "debug" => build_settings.debug = setting(key, value)?,
+            _ => anyhow::bail!(
+                "The bundle was built with the setting {} = {}, which is unknown to this version \
+                of cargo-contract, so the build can not be reproduced",
+                key,
+                value
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Rebuilds the contract at the given manifest path and verifies that it matches the bundle.
+///
+/// The contract is rebuilt with the settings recorded in the `source.buildInfo` of the bundle,
+/// into `target/ink/verify` to leave the artifacts of the user's builds untouched. Returns an error listing the mismatching hash, language and compiler versions if the
+/// local build differs from the bundle.
+pub(crate) fn execute(
+    bundle_path: &PathBuf,
+    manifest_path: &ManifestPath,
+    verbosity: Option<Verbosity>,
+    unstable_options: UnstableFlags,
+) -> Result<VerificationResult> {
+    let bundle = fs::read(bundle_path)
+        .context(format!("Failed to read bundle {}", bundle_path.display()))?;
+    let bundle: ContractMetadata = serde_json::from_slice(&bundle)
+        .context(format!("Failed to parse bundle {}", bundle_path.display()))?;
+
+    let mut crate_metadata = CrateMetadata::collect(manifest_path)?;
+    crate_metadata.use_verify_target_directory();
+    if let Some(build_info) = bundle.source().build_info() {
+        apply_build_info(&mut crate_metadata.build_settings, build_info)?;
+        if crate_metadata.build_settings.debug {
+            crate_metadata.use_debug_target_directory();
+        }
+    }
+    let (_, hash, _) = metadata::wasm_hash(
+        &crate_metadata,
+        verbosity,
+        BuildArtifacts::CodeOnly,
+        &BuildOptions::default(),
+        unstable_options,
+    )?;
+    let rust_version = Version::parse(&rustc_version::version()?.to_string())?;
+    let local = Source::new(
+        None,
+        hash.clone(),
+        SourceLanguage::new(Language::Ink, crate_metadata.ink_version),
+        SourceCompiler::new(Compiler::RustC, rust_version),
+    );
+
+    let mismatches = compare_sources(bundle.source(), &local);
+    if !mismatches.is_empty() {
+        let details = mismatches
+            .iter()
+            .map(|mismatch| {
+                format!(
+                    "  {}: bundle {}, local {}",
+                    mismatch.attribute, mismatch.bundle, mismatch.local
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        anyhow::bail!(
+            "The local build does not match {}:\n{}",
+            bundle_path.display(),
+            details
+        );
+    }
+    Ok(VerificationResult {
+        hash,
+        bundle: bundle_path.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_metadata::OptimizationPasses;
+    use pretty_assertions::assert_eq;
+
+    fn source(hash: u8, ink: &str, rustc: &str) -> Source {
+        Source::new(
+            None,
+            CodeHash([hash; 32]),
+            SourceLanguage::new(Language::Ink, Version::parse(ink).unwrap()),
+            SourceCompiler::new(Compiler::RustC, Version::parse(rustc).unwrap()),
+        )
+    }
+
+    #[test]
+    fn matching_sources_have_no_mismatches() {
+        let bundle = source(1, "3.0.0-rc2", "1.49.0-nightly");
+
+        assert_eq!(compare_sources(&bundle, &bundle.clone()), Vec::new());
+    }
+
+    #[test]
+    fn reports_every_mismatching_attribute() {
+        let bundle = source(1, "3.0.0-rc2", "1.49.0-nightly");
+        let local = source(2, "3.0.0-rc3", "1.50.0-nightly");
+
+        let mismatches = compare_sources(&bundle, &local);
+
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(|mismatch| mismatch.attribute)
+                .collect::<Vec<_>>(),
+            vec!["hash", "language", "compiler"]
+        );
+        assert_eq!(mismatches[1].bundle, "ink! 3.0.0-rc2");
+        assert_eq!(mismatches[2].local, "rustc 1.50.0-nightly");
+    }
+
+    #[test]
+    fn build_info_of_the_bundle_is_applied_to_the_rebuild() {
+        let bundle_settings = BuildSettings {
+            max_memory_pages: 32,
+            stack_size: 1024,
+            link_args: vec!["--no-entry".into()],
+            optimization_passes: OptimizationPasses::Z,
+            skip_optimization: true,
+            debug: true,
+            ..Default::default()
+        };
+        let mut settings = BuildSettings::default();
+
+        apply_build_info(&mut settings, &metadata::build_info(&bundle_settings)).unwrap();
+
+        assert_eq!(settings, bundle_settings);
+    }
+
+    #[test]
+    fn unknown_build_info_settings_are_rejected() {
+        let mut build_info = metadata::build_info(&BuildSettings::default());
+        build_info.insert("bulkMemory".into(), true.into());
+
+        let error = apply_build_info(&mut BuildSettings::default(), &build_info).unwrap_err();
+
+        assert!(error.to_string().contains("bulkMemory"));
+    }
+}
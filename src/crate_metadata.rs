@@ -18,8 +18,13 @@ use crate::ManifestPath;
 use anyhow::{Context, Result};
 use cargo_metadata::{Metadata as CargoMetadata, MetadataCommand, Package};
 use semver::Version;
+use serde::Deserialize;
 use serde_json::{Map, Value};
-use std::{fs, path::PathBuf};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use toml::value;
 use url::Url;
 
@@ -36,7 +41,269 @@ pub struct CrateMetadata {
     pub documentation: Option<Url>,
     pub homepage: Option<Url>,
     pub user: Option<Map<String, Value>>,
+    pub build_settings: BuildSettings,
+    /// The maximum size of the optimized Wasm, from `max-code-size` in
+    /// `[package.metadata.contract]`.
+    pub max_code_size: Option<CodeSize>,
     pub target_directory: PathBuf,
+    /// The directory the artifacts are written to, the `target_directory` unless the contract is
+    /// built as part of a workspace.
+    pub artifact_directory: PathBuf,
+}
+
+/// Settings for building the contract, recorded in the `source.buildInfo` of the metadata.
+///
+/// Chains differ in the limits of their contracts `Schedule`, so these have to match the chain
+/// the contract is deployed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildSettings {
+    /// The maximum number of memory pages available for the contract to allocate.
+    pub max_memory_pages: u32,
+    /// The size of the stack of the contract in bytes.
+    pub stack_size: u32,
+    /// Additional args passed to the linker.
+    pub link_args: Vec<String>,
+    /// The binaryen optimization passes applied to the Wasm.
+    ///
+    /// From `optimization-passes` in `[package.metadata.contract]`.
+    pub optimization_passes: OptimizationPasses,
+    /// Skip the optimization of the Wasm with binaryen.
+    ///
+    /// From `skip-optimization` in `[package.metadata.contract]`.
+    pub skip_optimization: bool,
+    /// Build with debug assertions and panic messages, keeping the `name` section.
+    ///
+    /// Only set by `build --debug`, not configurable in the manifest.
+    pub debug: bool,
+}
+
+impl Default for BuildSettings {
+    fn default() -> Self {
+        Self {
+            max_memory_pages: 16,
+            stack_size: 65536,
+            link_args: Vec::new(),
+            optimization_passes: OptimizationPasses::default(),
+            skip_optimization: false,
+            debug: false,
+        }
+    }
+}
+
+/// The `[package.metadata.contract.build]` section of Cargo.toml.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+struct BuildSection {
+    max_memory_pages: Option<u32>,
+    stack_size: Option<u32>,
+    link_args: Vec<String>,
+}
+
+impl BuildSection {
+    /// Applies the settings of the section to the defaults.
+    fn into_build_settings(self) -> BuildSettings {
+        let defaults = BuildSettings::default();
+        BuildSettings {
+            max_memory_pages: self.max_memory_pages.unwrap_or(defaults.max_memory_pages),
+            stack_size: self.stack_size.unwrap_or(defaults.stack_size),
+            link_args: self.link_args,
+            ..defaults
+        }
+    }
+}
+
+/// The `--locked`, `--frozen` and `--offline` flags of `build`, passed through to cargo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CargoFlags {
+    /// Require the `Cargo.lock` to be up to date.
+    pub locked: bool,
+    /// Require the `Cargo.lock` and the cache to be up to date.
+    pub frozen: bool,
+    /// Build without accessing the network.
+    pub offline: bool,
+}
+
+impl CargoFlags {
+    /// Returns the args for building the contract.
+    pub fn args(&self) -> Vec<&'static str> {
+        let flags = [
+            (self.locked, "--locked"),
+            (self.frozen, "--frozen"),
+            (self.offline, "--offline"),
+        ];
+        flags
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, arg)| *arg)
+            .collect()
+    }
+
+    /// Returns the args for running the metadata generation.
+    ///
+    /// The generated `metadata-gen` package is added to the `Cargo.lock`, so only the network
+    /// access is restricted.
+    pub fn metadata_args(&self) -> Vec<&'static str> {
+        if self.frozen || self.offline {
+            vec!["--offline"]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// The binaryen optimization passes, like the `-O<level>` flags of `wasm-opt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptimizationPasses {
+    Zero,
+    One,
+    Two,
+    /// The default, which applies shrink level 1 like the optimization of previous versions.
+    Three,
+    /// Optimize for size.
+    S,
+    /// Optimize aggressively for size.
+    Z,
+}
+
+impl OptimizationPasses {
+    /// Returns the binaryen optimization level.
+    pub fn optimization_level(&self) -> u32 {
+        match self {
+            OptimizationPasses::Zero => 0,
+            OptimizationPasses::One => 1,
+            OptimizationPasses::Two | OptimizationPasses::S | OptimizationPasses::Z => 2,
+            OptimizationPasses::Three => 3,
+        }
+    }
+
+    /// Returns the binaryen shrink level.
+    pub fn shrink_level(&self) -> u32 {
+        match self {
+            OptimizationPasses::Three | OptimizationPasses::S => 1,
+            OptimizationPasses::Z => 2,
+            _ => 0,
+        }
+    }
+}
+
+// deriving it requires `#[default]` on the variant, which is only supported since Rust 1.62
+#[allow(clippy::derivable_impls)]
+impl Default for OptimizationPasses {
+    fn default() -> Self {
+        OptimizationPasses::Three
+    }
+}
+
+impl FromStr for OptimizationPasses {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "0" => Ok(OptimizationPasses::Zero),
+            "1" => Ok(OptimizationPasses::One),
+            "2" => Ok(OptimizationPasses::Two),
+            "3" => Ok(OptimizationPasses::Three),
+            "s" => Ok(OptimizationPasses::S),
+            "z" => Ok(OptimizationPasses::Z),
+            _ => anyhow::bail!(
+                "Invalid optimization passes '{}', expected one of 0, 1, 2, 3, s, z",
+                input
+            ),
+        }
+    }
+}
+
+impl fmt::Display for OptimizationPasses {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let passes = match self {
+            OptimizationPasses::Zero => "0",
+            OptimizationPasses::One => "1",
+            OptimizationPasses::Two => "2",
+            OptimizationPasses::Three => "3",
+            OptimizationPasses::S => "s",
+            OptimizationPasses::Z => "z",
+        };
+        write!(f, "{}", passes)
+    }
+}
+
+impl<'de> Deserialize<'de> for OptimizationPasses {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        /// The numeric passes can be given as integers as well.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Passes {
+            Level(u8),
+            Name(String),
+        }
+
+        let passes = match Passes::deserialize(deserializer)? {
+            Passes::Level(level) => level.to_string(),
+            Passes::Name(name) => name,
+        };
+        passes.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A code size in bytes, given as an integer or with a `K` or `M` suffix like `32K`.
+///
+/// Like the sizes reported by the build, a kilobyte are 1000 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeSize(pub u64);
+
+impl CodeSize {
+    /// Returns the size in kilobytes.
+    pub fn kilobytes(&self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+}
+
+impl FromStr for CodeSize {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let input = input.trim();
+        let (number, unit) = match input.chars().last() {
+            Some('k') | Some('K') => (&input[..input.len() - 1], 1000),
+            Some('m') | Some('M') => (&input[..input.len() - 1], 1000 * 1000),
+            _ => (input, 1),
+        };
+        let size = number
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .and_then(|number| number.checked_mul(unit))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid code size '{}', expected bytes or a size like 32K",
+                    input
+                )
+            })?;
+        Ok(CodeSize(size))
+    }
+}
+
+impl<'de> Deserialize<'de> for CodeSize {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        /// The size can be given in bytes as an integer as well.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Size {
+            Bytes(u64),
+            Text(String),
+        }
+
+        match Size::deserialize(deserializer)? {
+            Size::Bytes(bytes) => Ok(CodeSize(bytes)),
+            Size::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
 }
 
 impl CrateMetadata {
@@ -48,17 +315,7 @@ impl CrateMetadata {
         // Normalize the package name.
         let package_name = root_package.name.replace("-", "_");
 
-        // {target_dir}/wasm32-unknown-unknown/release/{package_name}.wasm
-        let mut original_wasm = target_directory.clone();
-        original_wasm.push("wasm32-unknown-unknown");
-        original_wasm.push("release");
-        original_wasm.push(package_name.clone());
-        original_wasm.set_extension("wasm");
-
-        // {target_dir}/{package_name}.wasm
-        let mut dest_wasm = target_directory.clone();
-        dest_wasm.push(package_name.clone());
-        dest_wasm.set_extension("wasm");
+        let (original_wasm, dest_wasm) = wasm_paths(&target_directory, &package_name);
 
         let ink_version = metadata
             .packages
@@ -75,7 +332,13 @@ impl CrateMetadata {
             })
             .ok_or_else(|| anyhow::anyhow!("No 'ink_lang' dependency found"))?;
 
-        let (documentation, homepage, user) = get_cargo_toml_metadata(manifest_path)?;
+        let ManifestMetadata {
+            documentation,
+            homepage,
+            user,
+            build_settings,
+            max_code_size,
+        } = get_cargo_toml_metadata(manifest_path)?;
 
         let crate_metadata = CrateMetadata {
             manifest_path: manifest_path.clone(),
@@ -88,10 +351,125 @@ impl CrateMetadata {
             documentation,
             homepage,
             user,
+            build_settings,
+            max_code_size,
+            artifact_directory: target_directory.clone(),
             target_directory,
         };
         Ok(crate_metadata)
     }
+
+    /// Returns the path of the symbol map written alongside the post processed Wasm.
+    pub fn symbol_map_path(&self) -> PathBuf {
+        // {target_dir}/{package_name}.symbols.json
+        self.dest_wasm.with_extension("symbols.json")
+    }
+
+    /// Moves all artifacts to `{target_dir}/debug`, so a debug build does not overwrite the
+    /// artifacts of a release build.
+    pub fn use_debug_target_directory(&mut self) {
+        self.use_target_subdirectory("debug");
+    }
+
+    /// Moves all artifacts to `{target_dir}/verify`, so the rebuild of `verify` does not overwrite
+    /// the artifacts of the user's builds.
+    pub fn use_verify_target_directory(&mut self) {
+        self.use_target_subdirectory("verify");
+    }
+
+    fn use_target_subdirectory(&mut self, name: &str) {
+        self.target_directory = self.target_directory.join(name);
+        self.artifact_directory = self.target_directory.clone();
+        let (original_wasm, dest_wasm) = wasm_paths(&self.target_directory, &self.package_name);
+        self.original_wasm = original_wasm;
+        self.dest_wasm = dest_wasm;
+    }
+
+    /// Moves the artifacts to `{target_dir}/{package_name}`, so the contracts of a workspace can
+    /// share the target directory without overwriting each others `metadata.json`.
+    pub fn use_artifact_subdirectory(&mut self) {
+        self.artifact_directory = self.target_directory.join(&self.package_name);
+        self.dest_wasm = self.artifact_directory.join(
+            self.dest_wasm
+                .file_name()
+                .expect("dest wasm is a file; qed"),
+        );
+    }
+}
+
+/// Returns the workspace members which are ink! contracts, i.e. have a `cdylib` target and a
+/// dependency on `ink_lang`.
+///
+/// If `packages` are given only those are returned, in the order of the workspace members.
+pub fn workspace_contracts(
+    manifest_path: &ManifestPath,
+    packages: &[String],
+) -> Result<Vec<Package>> {
+    let metadata = MetadataCommand::new()
+        .manifest_path(manifest_path.as_ref())
+        .exec()
+        .context("Error invoking `cargo metadata`")?;
+    let workspace_members = &metadata.workspace_members;
+    let members = metadata
+        .packages
+        .iter()
+        .filter(|package| workspace_members.contains(&package.id))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for name in packages {
+        match members.iter().find(|member| member.name == *name) {
+            Some(member) if !is_contract(member) => {
+                anyhow::bail!("Package '{}' is not an ink! contract", name)
+            }
+            Some(_) => (),
+            None => anyhow::bail!("Package '{}' is not a member of the workspace", name),
+        }
+    }
+    let contracts = members
+        .into_iter()
+        .filter(|member| {
+            if packages.is_empty() {
+                is_contract(member)
+            } else {
+                packages.contains(&member.name)
+            }
+        })
+        .collect::<Vec<_>>();
+    if contracts.is_empty() {
+        anyhow::bail!("No ink! contracts found in the workspace")
+    }
+    Ok(contracts)
+}
+
+fn is_contract(package: &Package) -> bool {
+    let cdylib = package
+        .targets
+        .iter()
+        .any(|target| target.crate_types.iter().any(|ty| ty == "cdylib"));
+    let ink_lang = package
+        .dependencies
+        .iter()
+        .any(|dependency| dependency.name == "ink_lang");
+    cdylib && ink_lang
+}
+
+/// Returns the paths of the Wasm built by cargo and the post processed Wasm in the target
+/// directory.
+fn wasm_paths(target_directory: &Path, package_name: &str) -> (PathBuf, PathBuf) {
+    // {target_dir}/wasm32-unknown-unknown/release/{package_name}.wasm
+    let mut original_wasm = target_directory.to_path_buf();
+    original_wasm.push("wasm32-unknown-unknown");
+    original_wasm.push("release");
+    original_wasm.push(package_name);
+    original_wasm.set_extension("wasm");
+
+    // {target_dir}/{package_name}.wasm
+    let mut dest_wasm = target_directory.to_path_buf();
+    dest_wasm.push(package_name);
+    dest_wasm.set_extension("wasm");
+
+    (original_wasm, dest_wasm)
 }
 
 /// Get the result of `cargo metadata`, together with the root package id.
@@ -118,10 +496,17 @@ fn get_cargo_metadata(manifest_path: &ManifestPath) -> Result<(CargoMetadata, Pa
     Ok((metadata, root_package))
 }
 
+/// Extra metadata not available via `cargo metadata`, read directly from `Cargo.toml`.
+struct ManifestMetadata {
+    documentation: Option<Url>,
+    homepage: Option<Url>,
+    user: Option<Map<String, Value>>,
+    build_settings: BuildSettings,
+    max_code_size: Option<CodeSize>,
+}
+
 /// Read extra metadata not available via `cargo metadata` directly from `Cargo.toml`
-fn get_cargo_toml_metadata(
-    manifest_path: &ManifestPath,
-) -> Result<(Option<Url>, Option<Url>, Option<Map<String, Value>>)> {
+fn get_cargo_toml_metadata(manifest_path: &ManifestPath) -> Result<ManifestMetadata> {
     let toml = fs::read_to_string(manifest_path)?;
     let toml: value::Table = toml::from_str(&toml)?;
 
@@ -151,5 +536,165 @@ fn get_cargo_toml_metadata(
         })
         .transpose()?;
 
-    Ok((documentation, homepage, user))
+    let mut build_settings = toml
+        .get("package")
+        .and_then(|v| v.get("metadata"))
+        .and_then(|v| v.get("contract"))
+        .and_then(|v| v.get("build"))
+        .map(|v| v.clone().try_into::<BuildSection>())
+        .transpose()
+        .context("Invalid [package.metadata.contract.build] section")?
+        .unwrap_or_default()
+        .into_build_settings();
+
+    if let Some(optimization_passes) = contract_setting(&toml, "optimization-passes")? {
+        build_settings.optimization_passes = optimization_passes;
+    }
+    if let Some(skip_optimization) = contract_setting(&toml, "skip-optimization")? {
+        build_settings.skip_optimization = skip_optimization;
+    }
+
+    Ok(ManifestMetadata {
+        documentation,
+        homepage,
+        user,
+        build_settings,
+        max_code_size: contract_setting(&toml, "max-code-size")?,
+    })
+}
+
+/// Reads a setting of the `[package.metadata.contract]` section, which is not part of the
+/// `[package.metadata.contract.build]` settings.
+fn contract_setting<T>(toml: &value::Table, key: &str) -> Result<Option<T>>
+where
+    T: serde::de::DeserializeOwned,
+{
+    toml.get("package")
+        .and_then(|v| v.get("metadata"))
+        .and_then(|v| v.get("contract"))
+        .and_then(|v| v.get(key))
+        .map(|v| v.clone().try_into())
+        .transpose()
+        .context(format!("Invalid {} in [package.metadata.contract]", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_settings(toml: &str) -> Result<BuildSettings> {
+        let section: BuildSection = toml::from_str(toml)?;
+        Ok(section.into_build_settings())
+    }
+
+    #[test]
+    fn build_settings_default_to_the_previous_limits() {
+        let settings = build_settings("").unwrap();
+
+        assert_eq!(settings, BuildSettings::default());
+        assert_eq!(settings.max_memory_pages, 16);
+        assert_eq!(settings.stack_size, 65536);
+        assert_eq!(settings.optimization_passes, OptimizationPasses::Three);
+    }
+
+    #[test]
+    fn build_settings_are_deserialized() {
+        let settings = build_settings(
+            r#"
+            max-memory-pages = 32
+            link-args = ["--no-entry"]
+            "#,
+        )
+        .unwrap();
+
+        let expected = BuildSettings {
+            max_memory_pages: 32,
+            link_args: vec!["--no-entry".into()],
+            ..Default::default()
+        };
+        assert_eq!(settings, expected);
+    }
+
+    #[test]
+    fn default_optimization_passes_match_the_previous_optimization() {
+        let passes = OptimizationPasses::default();
+
+        assert_eq!((passes.optimization_level(), passes.shrink_level()), (3, 1));
+    }
+
+    #[test]
+    fn optimization_passes_are_given_as_integer_or_string() {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct Contract {
+            optimization_passes: OptimizationPasses,
+        }
+        let passes = |toml| -> Result<OptimizationPasses> {
+            let contract: Contract = toml::from_str(toml)?;
+            Ok(contract.optimization_passes)
+        };
+
+        assert_eq!(
+            passes("optimization-passes = 0").unwrap(),
+            OptimizationPasses::Zero
+        );
+        assert_eq!(
+            passes("optimization-passes = \"2\"").unwrap(),
+            OptimizationPasses::Two
+        );
+        assert_eq!(
+            passes("optimization-passes = \"s\"").unwrap(),
+            OptimizationPasses::S
+        );
+        assert!(passes("optimization-passes = 4").is_err());
+        assert!(passes("optimization-passes = \"fast\"").is_err());
+    }
+
+    #[test]
+    fn code_sizes_are_parsed_in_bytes_or_with_a_unit() {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct Contract {
+            max_code_size: CodeSize,
+        }
+        let code_size = |toml| -> Result<CodeSize> {
+            let contract: Contract = toml::from_str(toml)?;
+            Ok(contract.max_code_size)
+        };
+
+        assert_eq!(
+            code_size(r#"max-code-size = "32K""#).unwrap(),
+            CodeSize(32000)
+        );
+        assert_eq!(
+            code_size(r#"max-code-size = "1m""#).unwrap(),
+            CodeSize(1000000)
+        );
+        assert_eq!(code_size("max-code-size = 4096").unwrap(), CodeSize(4096));
+        assert_eq!("12345".parse::<CodeSize>().unwrap().kilobytes(), 12.345);
+        assert!(code_size(r#"max-code-size = "32KiB""#).is_err());
+    }
+
+    #[test]
+    fn unknown_build_settings_are_rejected() {
+        assert!(build_settings("stack_size = 1024").is_err());
+        assert!(build_settings("optimization-passes = 3").is_err());
+        assert!(build_settings("skip-optimization = true").is_err());
+    }
+
+    #[test]
+    fn frozen_builds_generate_the_metadata_offline() {
+        let flags = CargoFlags {
+            frozen: true,
+            ..Default::default()
+        };
+        assert_eq!(flags.args(), vec!["--frozen"]);
+        assert_eq!(flags.metadata_args(), vec!["--offline"]);
+
+        let locked = CargoFlags {
+            locked: true,
+            ..Default::default()
+        };
+        assert!(locked.metadata_args().is_empty());
+    }
 }
@@ -14,14 +14,46 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
+/// Prints the progress of a command like `println!`.
+///
+/// In JSON output mode the progress is printed to stderr, so that stdout only contains the JSON
+/// result.
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::util::output_json() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+mod build_cache;
 mod cmd;
 mod crate_metadata;
+mod rustflags;
+mod sandbox;
+// only the commands interacting with a node use the transcoder
+#[cfg_attr(not(feature = "extrinsics"), allow(dead_code))]
+mod transcode;
 mod util;
+mod validate_wasm;
 mod workspace;
 
-use self::workspace::ManifestPath;
+use self::{
+    crate_metadata::{BuildSettings, CargoFlags, CodeSize, OptimizationPasses},
+    workspace::ManifestPath,
+};
 
-use crate::cmd::{BuildCommand, CheckCommand};
+use crate::cmd::{
+    BuildCommand, CheckCommand, RunCommand, SizeCommand, SymbolizeCommand, TestCommand,
+    VerifyCommand,
+};
+#[cfg(feature = "extrinsics")]
+use crate::{
+    cmd::{CallCommand, DeployCommand},
+    transcode::InkProject,
+};
 
 #[cfg(feature = "extrinsics")]
 use sp_core::{crypto::Pair, sr25519, H256};
@@ -31,6 +63,8 @@ use subxt::PairSigner;
 
 use anyhow::{Error, Result};
 use colored::Colorize;
+use contract_metadata::CodeHash;
+use serde::{Deserialize, Serialize};
 use structopt::{clap, StructOpt};
 
 #[derive(Debug, StructOpt)]
@@ -46,6 +80,9 @@ pub(crate) enum Opts {
 
 #[derive(Debug, StructOpt)]
 pub(crate) struct ContractArgs {
+    /// Output the result of the command as JSON, progress is printed to stderr
+    #[structopt(long, global = true)]
+    output_json: bool,
     #[structopt(subcommand)]
     cmd: Command,
 }
@@ -53,7 +90,6 @@ pub(crate) struct ContractArgs {
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub(crate) struct HexData(pub Vec<u8>);
 
-#[cfg(feature = "extrinsics")]
 impl std::str::FromStr for HexData {
     type Err = hex::FromHexError;
 
@@ -124,11 +160,85 @@ struct UnstableOptions {
     /// Use the original manifest (Cargo.toml), do not modify for build optimizations
     #[structopt(long = "unstable-options", short = "Z", number_of_values = 1)]
     options: Vec<String>,
+    /// Keep the temporary workspace with the amended manifests, to inspect them
+    #[structopt(long)]
+    keep_temp_workspace: bool,
+}
+
+/// Overrides of the build settings from `[package.metadata.contract.build]` in Cargo.toml.
+#[derive(Clone, Debug, Default, StructOpt)]
+pub struct BuildSettingsFlags {
+    /// Maximum number of memory pages available for the contract to allocate
+    #[structopt(long)]
+    max_memory_pages: Option<u32>,
+    /// Size of the stack of the contract in bytes
+    #[structopt(long)]
+    stack_size: Option<u32>,
+    /// Additional arg passed to the linker, appended to the configured `link-args`
+    #[structopt(long = "link-arg", number_of_values = 1)]
+    link_args: Vec<String>,
+}
+
+impl BuildSettingsFlags {
+    /// Applies the overrides to the settings from the manifest.
+    fn apply(&self, settings: &mut BuildSettings) {
+        if let Some(max_memory_pages) = self.max_memory_pages {
+            settings.max_memory_pages = max_memory_pages;
+        }
+        if let Some(stack_size) = self.stack_size {
+            settings.stack_size = stack_size;
+        }
+        settings.link_args.extend(self.link_args.iter().cloned());
+    }
+}
+
+/// Overrides of the build settings recorded in the metadata, given to `build` or `check`.
+#[derive(Clone, Debug, Default)]
+pub struct BuildSettingsOverrides {
+    /// The overrides of the `[package.metadata.contract.build]` settings.
+    flags: BuildSettingsFlags,
+    /// Overrides `optimization-passes` of the manifest.
+    optimization_passes: Option<OptimizationPasses>,
+    /// Overrides `skip-optimization` of the manifest.
+    skip_optimization: Option<bool>,
+    /// Build with debug assertions and panic messages.
+    debug: bool,
+}
+
+impl BuildSettingsOverrides {
+    /// Applies the overrides to the settings from the manifest.
+    fn apply(&self, settings: &mut BuildSettings) {
+        self.flags.apply(settings);
+        if let Some(optimization_passes) = self.optimization_passes {
+            settings.optimization_passes = optimization_passes;
+        }
+        if let Some(skip_optimization) = self.skip_optimization {
+            settings.skip_optimization = skip_optimization;
+        }
+        if self.debug {
+            settings.debug = true;
+        }
+    }
+}
+
+/// Switches of `build` which do not affect the build artifacts and so are not recorded in the
+/// metadata.
+#[derive(Clone, Debug, Default)]
+pub struct BuildOptions {
+    /// Overrides `max-code-size` of the manifest.
+    max_code_size: Option<CodeSize>,
+    /// Rebuild all steps, bypassing the build cache.
+    force: bool,
+    /// Skip the validation of the Wasm, see [`validate_wasm::validate`].
+    skip_validation: bool,
+    /// The flags passed through to cargo.
+    cargo_flags: CargoFlags,
 }
 
 #[derive(Clone, Default)]
 struct UnstableFlags {
     original_manifest: bool,
+    keep_temp_workspace: bool,
 }
 
 impl TryFrom<&UnstableOptions> for UnstableFlags {
@@ -146,13 +256,15 @@ impl TryFrom<&UnstableOptions> for UnstableFlags {
         }
         Ok(UnstableFlags {
             original_manifest: value.options.contains(&"original-manifest".to_owned()),
+            keep_temp_workspace: value.keep_temp_workspace,
         })
     }
 }
 
 /// Describes which artifacts to generate
-#[derive(Copy, Clone, Eq, PartialEq, Debug, StructOpt)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, StructOpt, Serialize)]
 #[structopt(name = "build-artifacts")]
+#[serde(rename_all = "kebab-case")]
 pub enum BuildArtifacts {
     /// Generate the Wasm, the metadata and a bundled `<name>.contract` file
     #[structopt(name = "all")]
@@ -187,6 +299,8 @@ impl std::str::FromStr for BuildArtifacts {
 }
 
 /// Result of the metadata generation process.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BuildResult {
     /// Path to the resulting metadata file.
     pub dest_metadata: Option<PathBuf>,
@@ -198,11 +312,76 @@ pub struct BuildResult {
     pub target_directory: PathBuf,
     /// If existent the result of the optimization.
     pub optimization_result: Option<OptimizationResult>,
+    /// If existent the hash of the resulting Wasm file.
+    pub code_hash: Option<CodeHash>,
     /// Which build artifacts were generated.
     pub build_artifact: BuildArtifacts,
 }
 
+/// Result of building the ink! contracts of a workspace.
+pub struct WorkspaceBuildResult {
+    /// The package name and build result of every contract, in the order they were built.
+    pub contracts: Vec<(String, BuildResult)>,
+}
+
+impl WorkspaceBuildResult {
+    /// Returns the results as pretty printed JSON, by package name.
+    pub fn serialize_json(&self) -> Result<String> {
+        let mut contracts = serde_json::Map::new();
+        for (name, result) in &self.contracts {
+            contracts.insert(name.clone(), serde_json::to_value(result)?);
+        }
+        let json = serde_json::json!({ "contracts": contracts });
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+
+    /// Returns a table with the optimized size, code hash and artifact directory of every contract.
+    pub fn display(&self) -> String {
+        let width = self
+            .contracts
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(std::iter::once("Contract".len()))
+            .max()
+            .unwrap_or_default();
+        let mut out = format!(
+            "\nYour contracts are ready:\n\n{}\n",
+            format!(
+                "{:<width$}  {:>9}  {:<20}  Artifacts",
+                "Contract",
+                "Optimized",
+                "Code hash",
+                width = width
+            )
+            .bold()
+        );
+        for (name, result) in &self.contracts {
+            let size = result
+                .optimization_result
+                .as_ref()
+                .map(|optimization| format!("{:.1}K", optimization.optimized_size))
+                .unwrap_or_default();
+            let code_hash = result
+                .code_hash
+                .as_ref()
+                .map(|hash| format!("0x{}..", hex::encode(&hash.0[..8])))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "{:<width$}  {:>9}  {:<20}  {}\n",
+                name,
+                size,
+                code_hash,
+                result.target_directory.display(),
+                width = width
+            ));
+        }
+        out
+    }
+}
+
 /// Result of the optimization process.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct OptimizationResult {
     /// The original Wasm size.
     pub original_size: f64,
@@ -211,6 +390,11 @@ pub struct OptimizationResult {
 }
 
 impl BuildResult {
+    /// Returns the result as pretty printed JSON.
+    pub fn serialize_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
     pub fn display(&self) -> String {
         let optimization = self.display_optimization();
         let size_diff = format!(
@@ -296,17 +480,23 @@ enum Command {
     Check(CheckCommand),
     /// Test the smart contract off-chain
     #[structopt(name = "test")]
-    Test {},
+    Test(TestCommand),
+    /// Execute a constructor and messages of the built contract in a local sandbox
+    #[structopt(name = "run")]
+    Run(RunCommand),
+    /// Verify that a `<name>.contract` bundle was built from the local sources
+    #[structopt(name = "verify")]
+    Verify(VerifyCommand),
+    /// Report which functions, data segments and crates make up the size of the contract's Wasm
+    #[structopt(name = "size")]
+    Size(SizeCommand),
+    /// Map function indices of a trapped contract back to the names of the functions
+    #[structopt(name = "symbolize")]
+    Symbolize(SymbolizeCommand),
     /// Upload the smart contract code to the chain
     #[cfg(feature = "extrinsics")]
     #[structopt(name = "deploy")]
-    Deploy {
-        #[structopt(flatten)]
-        extrinsic_opts: ExtrinsicOpts,
-        /// Path to wasm contract code, defaults to `./target/ink/<name>.wasm`
-        #[structopt(parse(from_os_str))]
-        wasm_path: Option<PathBuf>,
-    },
+    Deploy(DeployCommand),
     /// Instantiate a deployed smart contract
     #[cfg(feature = "extrinsics")]
     #[structopt(name = "instantiate")]
@@ -323,9 +513,31 @@ enum Command {
         #[structopt(long, parse(try_from_str = parse_code_hash))]
         code_hash: H256,
         /// Hex encoded data to call a contract constructor
-        #[structopt(long)]
-        data: HexData,
+        #[structopt(long, required_unless = "constructor")]
+        data: Option<HexData>,
+        /// Name of the constructor to call, the call data is encoded using the contract metadata
+        #[structopt(long, conflicts_with = "data")]
+        constructor: Option<String>,
+        /// Arguments of the constructor, given as JSON values or plain strings
+        #[structopt(long, requires = "constructor")]
+        args: Vec<String>,
     },
+    /// Call a message of an instantiated smart contract
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "call")]
+    Call(CallCommand),
+}
+
+impl Command {
+    /// Returns `true` if the result of the command can be output as JSON.
+    fn supports_json(&self) -> bool {
+        match self {
+            Command::Build(_) | Command::Check(_) => true,
+            #[cfg(feature = "extrinsics")]
+            Command::Deploy(_) | Command::Instantiate { .. } | Command::Call(_) => true,
+            _ => false,
+        }
+    }
 }
 
 #[cfg(feature = "extrinsics")]
@@ -343,8 +555,14 @@ fn main() {
     env_logger::init();
 
     let Opts::Contract(args) = Opts::from_args();
+    util::set_output_json(args.output_json);
     match exec(args.cmd) {
+        Ok(msg) if args.output_json => println!("{}", msg),
         Ok(msg) => println!("\t{}", msg),
+        Err(err) if args.output_json => {
+            println!("{}", error_json(&err));
+            std::process::exit(1);
+        }
         Err(err) => {
             eprintln!(
                 "{} {}",
@@ -356,11 +574,38 @@ fn main() {
     }
 }
 
+/// Returns the error and its causes as pretty printed JSON.
+fn error_json(err: &Error) -> String {
+    let causes = err
+        .chain()
+        .skip(1)
+        .map(|cause| cause.to_string())
+        .collect::<Vec<_>>();
+    let json = serde_json::json!({
+        "error": err.to_string(),
+        "causes": causes,
+    });
+    serde_json::to_string_pretty(&json).expect("serializing a JSON value cannot fail; qed")
+}
+
 fn exec(cmd: Command) -> Result<String> {
+    if util::output_json() && !cmd.supports_json() {
+        anyhow::bail!("This command does not support `--output-json`")
+    }
     match &cmd {
         Command::New { name, target_dir } => cmd::new::execute(name, target_dir.as_ref()),
+        Command::Build(build) if build.builds_workspace() => {
+            let result = build.exec_workspace()?;
+            if util::output_json() {
+                return result.serialize_json();
+            }
+            Ok(result.display())
+        }
         Command::Build(build) => {
             let result = build.exec()?;
+            if util::output_json() {
+                return result.serialize_json();
+            }
             Ok(result.display())
         }
         Command::Check(check) => {
@@ -369,20 +614,27 @@ fn exec(cmd: Command) -> Result<String> {
                 res.dest_wasm.is_none(),
                 "no dest_wasm must be on the generation result"
             );
+            if util::output_json() {
+                return res.serialize_json();
+            }
             Ok("\nYour contract's code was built successfully.".to_string())
         }
         Command::GenerateMetadata {} => Err(anyhow::anyhow!(
             "Command deprecated, use `cargo contract build` instead"
         )),
-        Command::Test {} => Err(anyhow::anyhow!("Command unimplemented")),
-        #[cfg(feature = "extrinsics")]
-        Command::Deploy {
-            extrinsic_opts,
-            wasm_path,
-        } => {
-            let code_hash = cmd::execute_deploy(extrinsic_opts, wasm_path.as_ref())?;
-            Ok(format!("Code hash: {:?}", code_hash))
+        Command::Test(test) => {
+            let res = test.exec()?;
+            Ok(res.display())
         }
+        Command::Run(run) => run.exec(),
+        Command::Verify(verify) => {
+            let res = verify.exec()?;
+            Ok(res.display())
+        }
+        Command::Size(size) => size.exec(),
+        Command::Symbolize(symbolize) => symbolize.exec(),
+        #[cfg(feature = "extrinsics")]
+        Command::Deploy(deploy) => deploy.exec(),
         #[cfg(feature = "extrinsics")]
         Command::Instantiate {
             extrinsic_opts,
@@ -390,15 +642,123 @@ fn exec(cmd: Command) -> Result<String> {
             code_hash,
             gas_limit,
             data,
+            constructor,
+            args,
         } => {
-            let contract_account = cmd::execute_instantiate(
-                extrinsic_opts,
-                *endowment,
-                *gas_limit,
-                *code_hash,
-                data.clone(),
-            )?;
-            Ok(format!("Contract account: {:?}", contract_account))
+            let data = match (data, constructor) {
+                (Some(data), _) => data.clone(),
+                (None, Some(constructor)) => {
+                    HexData(InkProject::load_default()?.encode_constructor(constructor, args)?)
+                }
+                (None, None) => unreachable!("either --data or --constructor is required"),
+            };
+            let (contract_account, events) =
+                cmd::execute_instantiate(extrinsic_opts, *endowment, *gas_limit, *code_hash, data)?;
+            // contract events are displayed undecoded if no metadata is available
+            let project = InkProject::load_default().ok();
+            if util::output_json() {
+                let json = serde_json::json!({
+                    "contract": contract_account,
+                    "events": cmd::events_json(&events, project.as_ref()),
+                });
+                return Ok(serde_json::to_string_pretty(&json)?);
+            }
+            Ok(format!(
+                "Contract account: {:?}\nEvents:\n\t{}",
+                contract_account,
+                cmd::display_events(&events, project.as_ref())
+            ))
         }
+        #[cfg(feature = "extrinsics")]
+        Command::Call(call) => call.exec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_result_serializes_to_json() {
+        let result = BuildResult {
+            dest_metadata: Some(PathBuf::from("target/ink/metadata.json")),
+            dest_wasm: Some(PathBuf::from("target/ink/flipper.wasm")),
+            dest_bundle: Some(PathBuf::from("target/ink/flipper.contract")),
+            target_directory: PathBuf::from("target/ink"),
+            optimization_result: Some(OptimizationResult {
+                original_size: 64.0,
+                optimized_size: 32.5,
+            }),
+            code_hash: Some(CodeHash([1u8; 32])),
+            build_artifact: BuildArtifacts::All,
+        };
+
+        let json: serde_json::Value =
+            serde_json::from_str(&result.serialize_json().unwrap()).unwrap();
+
+        let expected = serde_json::json!({
+            "destMetadata": "target/ink/metadata.json",
+            "destWasm": "target/ink/flipper.wasm",
+            "destBundle": "target/ink/flipper.contract",
+            "targetDirectory": "target/ink",
+            "optimizationResult": {
+                "originalSize": 64.0,
+                "optimizedSize": 32.5,
+            },
+            "codeHash": format!("0x{}", "01".repeat(32)),
+            "buildArtifact": "all",
+        });
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn workspace_build_result_lists_every_contract() {
+        let result = |name: &str| BuildResult {
+            dest_metadata: None,
+            dest_wasm: Some(PathBuf::from(format!("target/ink/{0}/{0}.wasm", name))),
+            dest_bundle: None,
+            target_directory: PathBuf::from(format!("target/ink/{}", name)),
+            optimization_result: Some(OptimizationResult {
+                original_size: 64.0,
+                optimized_size: 32.5,
+            }),
+            code_hash: Some(CodeHash([0xab; 32])),
+            build_artifact: BuildArtifacts::CodeOnly,
+        };
+        let result = WorkspaceBuildResult {
+            contracts: vec![
+                ("flipper".to_string(), result("flipper")),
+                ("erc20".to_string(), result("erc20")),
+            ],
+        };
+
+        let json: serde_json::Value =
+            serde_json::from_str(&result.serialize_json().unwrap()).unwrap();
+        let table = result.display();
+
+        assert_eq!(
+            json["contracts"]["erc20"]["targetDirectory"],
+            "target/ink/erc20"
+        );
+        assert_eq!(
+            table.lines().skip(4).collect::<Vec<_>>(),
+            vec![
+                "flipper       32.5K  0xabababababababab..  target/ink/flipper",
+                "erc20         32.5K  0xabababababababab..  target/ink/erc20",
+            ]
+        );
+    }
+
+    #[test]
+    fn error_serializes_to_json_with_causes() {
+        let err = anyhow::anyhow!("file not found").context("Failed to read the bundle");
+
+        let json: serde_json::Value = serde_json::from_str(&error_json(&err)).unwrap();
+
+        let expected = serde_json::json!({
+            "error": "Failed to read the bundle",
+            "causes": ["file not found"],
+        });
+        assert_eq!(json, expected);
     }
 }
@@ -0,0 +1,314 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{crate_metadata::BuildSettings, util};
+use anyhow::{Context, Result};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use toml::Value;
+
+/// The target the contract is compiled for.
+const TARGET: &str = "wasm32-unknown-unknown";
+
+/// Returns the `CARGO_ENCODED_RUSTFLAGS` for building the contract in the given directory.
+///
+/// The flags configured by the user are taken from the first of these sources which is set, the
+/// same way cargo does:
+///
+/// 1. the `CARGO_ENCODED_RUSTFLAGS` environment variable
+/// 2. the `RUSTFLAGS` environment variable
+/// 3. the `target.wasm32-unknown-unknown.rustflags` of the cargo config files
+/// 4. the `build.rustflags` of the cargo config files
+///
+/// The `rustflags` of `[target.'cfg(..)']` sections are not supported and ignored.
+///
+/// The linker args required for contracts, see [`link_args`], are appended to those flags. Debug
+/// builds enable the debug assertions as well.
+///
+/// The returned flags are separated by `\x1f`, the way cargo expects them in
+/// `CARGO_ENCODED_RUSTFLAGS`, so they may contain whitespace.
+pub(crate) fn contract_rustflags(dir: &Path, settings: &BuildSettings) -> Result<String> {
+    let mut flags = match env_rustflags() {
+        Some(flags) => flags,
+        None => {
+            let configs = config_files(dir)?
+                .iter()
+                .map(|path| read_config(path))
+                .collect::<Result<Vec<_>>>()?;
+            config_rustflags(&configs)?.unwrap_or_default()
+        }
+    };
+    if settings.debug {
+        flags.extend(vec!["-C".to_string(), "debug-assertions=on".to_string()]);
+    }
+    flags.extend(link_args(settings));
+    Ok(flags.join("\x1f"))
+}
+
+/// Returns the linker args for the stack size, the imported memory and the additional link args
+/// of the contract.
+fn link_args(settings: &BuildSettings) -> Vec<String> {
+    let stack_size = format!("stack-size={}", settings.stack_size);
+    ["-z", &stack_size, "--import-memory"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .chain(settings.link_args.iter().cloned())
+        .flat_map(|arg| vec!["-C".to_string(), format!("link-arg={}", arg)])
+        .collect()
+}
+
+/// Returns the flags of the `CARGO_ENCODED_RUSTFLAGS` or `RUSTFLAGS` environment variables.
+fn env_rustflags() -> Option<Vec<String>> {
+    if let Ok(encoded) = env::var("CARGO_ENCODED_RUSTFLAGS") {
+        if encoded.is_empty() {
+            return Some(Vec::new());
+        }
+        return Some(encoded.split('\x1f').map(str::to_string).collect());
+    }
+    env::var("RUSTFLAGS")
+        .ok()
+        .map(|flags| flags.split_whitespace().map(str::to_string).collect())
+}
+
+/// Returns the cargo config files which apply to the given directory, the deepest first.
+///
+/// Like cargo, these are the `.cargo/config` or `.cargo/config.toml` files of the directory and
+/// all of its ancestors, followed by the one in `CARGO_HOME`.
+fn config_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let dir = fs::canonicalize(dir).context(format!("Failed to resolve {}", dir.display()))?;
+    let cargo_home = util::cargo_home();
+
+    let mut config_dirs = dir
+        .ancestors()
+        .map(|ancestor| ancestor.join(".cargo"))
+        .collect::<Vec<_>>();
+    if let Some(cargo_home) = cargo_home {
+        if !config_dirs.contains(&cargo_home) {
+            config_dirs.push(cargo_home);
+        }
+    }
+    let files = config_dirs
+        .iter()
+        .filter_map(|config_dir| {
+            // cargo prefers the file without extension if both exist
+            vec![config_dir.join("config"), config_dir.join("config.toml")]
+                .into_iter()
+                .find(|file| file.is_file())
+        })
+        .collect();
+    Ok(files)
+}
+
+fn read_config(path: &Path) -> Result<Value> {
+    let contents =
+        fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    toml::from_str(&contents).context(format!("Failed to parse {}", path.display()))
+}
+
+/// Returns the rustflags of the given cargo configs, the deepest first.
+///
+/// The `target.wasm32-unknown-unknown.rustflags` take precedence over the `build.rustflags`, the
+/// `target.'cfg(..)'.rustflags` are ignored.
+///
+/// The configs are merged like cargo does: arrays are joined with the flags of the deeper configs
+/// last, so they override the ones of their ancestors, while the string of the deepest config
+/// replaces the ones of its ancestors. A string and an array can not be merged.
+fn config_rustflags(configs: &[Value]) -> Result<Option<Vec<String>>> {
+    let target = configs
+        .iter()
+        .filter_map(|config| config.get("target")?.get(TARGET)?.get("rustflags"))
+        .collect::<Vec<_>>();
+    let build = configs
+        .iter()
+        .filter_map(|config| config.get("build")?.get("rustflags"))
+        .collect::<Vec<_>>();
+    let values = if !target.is_empty() { target } else { build };
+
+    let mut merged: Option<Vec<String>> = None;
+    let mut merged_string = false;
+    for value in values.into_iter().rev() {
+        match value {
+            Value::String(s) => {
+                if merged.is_some() && !merged_string {
+                    anyhow::bail!("Failed to merge the rustflags {} with an array", value)
+                }
+                merged = Some(s.split_whitespace().map(str::to_string).collect());
+                merged_string = true;
+            }
+            Value::Array(array) => {
+                if merged_string {
+                    anyhow::bail!("Failed to merge the rustflags {} with a string", value)
+                }
+                let flags = merged.get_or_insert_with(Vec::new);
+                for flag in array {
+                    let flag = flag.as_str().ok_or_else(|| {
+                        anyhow::anyhow!("Expected rustflags as strings, got {}", flag)
+                    })?;
+                    flags.push(flag.to_string());
+                }
+            }
+            _ => anyhow::bail!("Expected rustflags as string or array, got {}", value),
+        }
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::with_tmp_dir;
+
+    fn config(toml: &str) -> Value {
+        toml::from_str(toml).expect("invalid toml")
+    }
+
+    #[test]
+    fn target_rustflags_take_precedence_over_build_rustflags() {
+        let configs = [
+            config(
+                r#"
+                [build]
+                rustflags = ["--cfg", "build"]
+                "#,
+            ),
+            config(
+                r#"
+                [target.wasm32-unknown-unknown]
+                rustflags = "-C target-cpu=mvp"
+                "#,
+            ),
+        ];
+
+        let flags = config_rustflags(&configs).unwrap();
+
+        assert_eq!(flags, Some(vec!["-C".into(), "target-cpu=mvp".into()]));
+    }
+
+    #[test]
+    fn rustflags_arrays_are_joined_with_the_deeper_flags_last() {
+        let configs = [
+            config(
+                r#"
+                [build]
+                rustflags = ["--cfg", "deeper"]
+                "#,
+            ),
+            config(
+                r#"
+                [target.x86_64-unknown-linux-gnu]
+                rustflags = ["--cfg", "native"]
+                "#,
+            ),
+            config(
+                r#"
+                [build]
+                rustflags = ["--cfg", "ancestor"]
+                "#,
+            ),
+        ];
+
+        let flags = config_rustflags(&configs).unwrap();
+
+        let expected = ["--cfg", "ancestor", "--cfg", "deeper"];
+        assert_eq!(
+            flags,
+            Some(expected.iter().map(|f| f.to_string()).collect())
+        );
+        assert_eq!(config_rustflags(&configs[1..2]).unwrap(), None);
+    }
+
+    #[test]
+    fn rustflags_string_of_the_deepest_config_wins() {
+        let configs = [
+            config(
+                r#"
+                [build]
+                rustflags = "--cfg deeper"
+                "#,
+            ),
+            config(
+                r#"
+                [build]
+                rustflags = "--cfg ancestor"
+                "#,
+            ),
+        ];
+
+        let flags = config_rustflags(&configs).unwrap();
+
+        assert_eq!(flags, Some(vec!["--cfg".into(), "deeper".into()]));
+    }
+
+    #[test]
+    fn rustflags_string_and_array_are_not_merged() {
+        let string = config(
+            r#"
+            [build]
+            rustflags = "--cfg string"
+            "#,
+        );
+        let array = config(
+            r#"
+            [build]
+            rustflags = ["--cfg", "array"]
+            "#,
+        );
+
+        assert!(config_rustflags(&[string.clone(), array.clone()]).is_err());
+        assert!(config_rustflags(&[array, string]).is_err());
+    }
+
+    #[test]
+    fn link_args_include_stack_size_and_additional_args() {
+        let settings = BuildSettings {
+            stack_size: 32768,
+            link_args: vec!["--no-entry".into()],
+            ..Default::default()
+        };
+
+        let flags = link_args(&settings).join(" ");
+
+        assert_eq!(
+            flags,
+            "-C link-arg=-z -C link-arg=stack-size=32768 -C link-arg=--import-memory \
+             -C link-arg=--no-entry"
+        );
+    }
+
+    #[test]
+    fn config_files_of_ancestors_are_found_deepest_first() {
+        with_tmp_dir(|path| {
+            let nested = path.join("workspace").join("contract");
+            fs::create_dir_all(nested.join(".cargo"))?;
+            fs::create_dir_all(path.join(".cargo"))?;
+            fs::write(nested.join(".cargo").join("config.toml"), "")?;
+            fs::write(path.join(".cargo").join("config"), "")?;
+            fs::write(path.join(".cargo").join("config.toml"), "")?;
+
+            let files = config_files(&nested)?;
+
+            let path = fs::canonicalize(path)?;
+            let expected = [
+                path.join("workspace/contract/.cargo/config.toml"),
+                path.join(".cargo/config"),
+            ];
+            assert_eq!(&files[..2], &expected[..]);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,423 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{cell::RefCell, fmt};
+
+use blake2::digest::{Update as _, VariableOutput as _};
+use codec::Encode;
+use sha2::Digest as _;
+use wasmi::{
+    memory_units::Pages, Error, Externals, FuncInstance, FuncRef, HostError, MemoryDescriptor,
+    MemoryInstance, MemoryRef, ModuleImportResolver, RuntimeArgs, RuntimeValue, Signature, Trap,
+    TrapKind,
+};
+
+use super::{
+    profile::{Profile, PROFILE_FUNCTION},
+    Balance, Event, Sandbox, Storage,
+};
+
+/// The host functions of the `seal0` module, the index of a function is its host function index.
+const HOST_FUNCTIONS: &[&str] = &[
+    "seal_set_storage",
+    "seal_clear_storage",
+    "seal_get_storage",
+    "seal_transfer",
+    "seal_call",
+    "seal_instantiate",
+    "seal_terminate",
+    "seal_input",
+    "seal_return",
+    "seal_caller",
+    "seal_address",
+    "seal_weight_to_fee",
+    "seal_gas_left",
+    "seal_balance",
+    "seal_value_transferred",
+    "seal_random",
+    "seal_now",
+    "seal_minimum_balance",
+    "seal_tombstone_deposit",
+    "seal_restore_to",
+    "seal_deposit_event",
+    "seal_set_rent_allowance",
+    "seal_rent_allowance",
+    "seal_println",
+    "seal_block_number",
+    "seal_hash_sha2_256",
+    "seal_hash_keccak_256",
+    "seal_hash_blake2_256",
+    "seal_hash_blake2_128",
+    "seal_call_chain_extension",
+];
+
+/// The host function index of the [`PROFILE_FUNCTION`] imported by instrumented modules.
+const PROFILE_FUNCTION_INDEX: usize = HOST_FUNCTIONS.len();
+
+/// Passed as output pointer by the contract if it is not interested in the output.
+const SENTINEL: u32 = u32::MAX;
+
+/// Return codes of the host functions, as defined by the contracts pallet.
+#[derive(Clone, Copy)]
+enum ReturnCode {
+    Success = 0,
+    KeyNotFound = 3,
+    TransferFailed = 5,
+}
+
+impl From<ReturnCode> for RuntimeValue {
+    fn from(code: ReturnCode) -> Self {
+        RuntimeValue::I32(code as i32)
+    }
+}
+
+/// The reason for a host function to stop the execution of the contract.
+#[derive(Debug)]
+pub(super) enum TrapReason {
+    /// The contract called `seal_return`.
+    Return { flags: u32, data: Vec<u8> },
+    /// The contract called `seal_terminate`.
+    Termination,
+    /// The contract used the host functions incorrectly, or used a function which is not
+    /// supported by the sandbox.
+    SupervisorError(String),
+}
+
+impl fmt::Display for TrapReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrapReason::Return { flags, .. } => write!(f, "seal_return with flags {}", flags),
+            TrapReason::Termination => write!(f, "seal_terminate"),
+            TrapReason::SupervisorError(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl HostError for TrapReason {}
+
+fn supervisor_error<S: Into<String>>(msg: S) -> Trap {
+    TrapReason::SupervisorError(msg.into()).into()
+}
+
+/// Resolves the `seal0` host functions and allocates the `env.memory` import.
+pub(super) struct Resolver {
+    profiling: bool,
+    memory: RefCell<Option<MemoryRef>>,
+}
+
+impl Resolver {
+    /// Creates a new resolver, which resolves the profiling import if `profiling` is enabled.
+    pub fn new(profiling: bool) -> Self {
+        Resolver {
+            profiling,
+            memory: Default::default(),
+        }
+    }
+
+    /// Returns the memory allocated for the `env.memory` import, if any.
+    pub fn memory(&self) -> Option<MemoryRef> {
+        self.memory.borrow().clone()
+    }
+}
+
+impl ModuleImportResolver for Resolver {
+    fn resolve_func(&self, field_name: &str, signature: &Signature) -> Result<FuncRef, Error> {
+        if self.profiling && field_name == PROFILE_FUNCTION {
+            return Ok(FuncInstance::alloc_host(
+                signature.clone(),
+                PROFILE_FUNCTION_INDEX,
+            ));
+        }
+        let index = HOST_FUNCTIONS
+            .iter()
+            .position(|name| *name == field_name)
+            .ok_or_else(|| {
+                Error::Instantiation(format!(
+                    "Host function `{}` is not supported by the sandbox",
+                    field_name
+                ))
+            })?;
+        Ok(FuncInstance::alloc_host(signature.clone(), index))
+    }
+
+    fn resolve_memory(
+        &self,
+        field_name: &str,
+        descriptor: &MemoryDescriptor,
+    ) -> Result<MemoryRef, Error> {
+        if field_name != "memory" {
+            return Err(Error::Instantiation(format!(
+                "Memory import `{}` is not supported, expected `memory`",
+                field_name
+            )));
+        }
+        let memory = MemoryInstance::alloc(
+            Pages(descriptor.initial() as usize),
+            descriptor.maximum().map(|maximum| Pages(maximum as usize)),
+        )?;
+        *self.memory.borrow_mut() = Some(memory.clone());
+        Ok(memory)
+    }
+}
+
+/// The state of a single contract execution, implementing the `seal0` host functions.
+pub(super) struct Runtime<'a> {
+    sandbox: &'a Sandbox,
+    memory: MemoryRef,
+    input: Vec<u8>,
+    value: Balance,
+    storage: Storage,
+    balance: Balance,
+    events: Vec<Event>,
+    debug_messages: Vec<String>,
+    profile: Profile,
+}
+
+impl<'a> Runtime<'a> {
+    pub fn new(sandbox: &'a Sandbox, memory: MemoryRef, input: Vec<u8>, value: Balance) -> Self {
+        Runtime {
+            sandbox,
+            memory,
+            input,
+            value,
+            storage: sandbox.storage.clone(),
+            balance: sandbox.balance.saturating_add(value),
+            events: Vec::new(),
+            debug_messages: Vec::new(),
+            profile: Default::default(),
+        }
+    }
+
+    /// Returns the resulting `(storage, balance, events, debug_messages, profile)` of the
+    /// execution.
+    pub fn into_parts(self) -> (Storage, Balance, Vec<Event>, Vec<String>, Profile) {
+        (
+            self.storage,
+            self.balance,
+            self.events,
+            self.debug_messages,
+            self.profile,
+        )
+    }
+
+    fn read(&self, ptr: u32, len: u32) -> Result<Vec<u8>, Trap> {
+        self.memory
+            .get(ptr, len as usize)
+            .map_err(|_| TrapKind::MemoryAccessOutOfBounds.into())
+    }
+
+    fn read_key(&self, ptr: u32) -> Result<[u8; 32], Trap> {
+        let mut key = [0u8; 32];
+        self.memory
+            .get_into(ptr, &mut key)
+            .map_err(|_| Trap::from(TrapKind::MemoryAccessOutOfBounds))?;
+        Ok(key)
+    }
+
+    fn write(&self, ptr: u32, data: &[u8]) -> Result<(), Trap> {
+        self.memory
+            .set(ptr, data)
+            .map_err(|_| TrapKind::MemoryAccessOutOfBounds.into())
+    }
+
+    /// Writes `data` to the output buffer at `out_ptr` and its length to `out_len_ptr`.
+    ///
+    /// The buffer length must initially be stored at `out_len_ptr`. Nothing is written if
+    /// `out_ptr` is the sentinel value.
+    fn write_output(&self, out_ptr: u32, out_len_ptr: u32, data: &[u8]) -> Result<(), Trap> {
+        if out_ptr == SENTINEL {
+            return Ok(());
+        }
+        let buf_len = self
+            .memory
+            .get_value::<u32>(out_len_ptr)
+            .map_err(|_| Trap::from(TrapKind::MemoryAccessOutOfBounds))?;
+        if (buf_len as usize) < data.len() {
+            return Err(supervisor_error(format!(
+                "Output buffer of {} bytes is too small for {} bytes",
+                buf_len,
+                data.len()
+            )));
+        }
+        self.write(out_ptr, data)?;
+        self.memory
+            .set_value(out_len_ptr, data.len() as u32)
+            .map_err(|_| TrapKind::MemoryAccessOutOfBounds.into())
+    }
+
+    fn read_balance(&self, ptr: u32, len: u32) -> Result<Balance, Trap> {
+        let bytes = self.read(ptr, len)?;
+        codec::Decode::decode(&mut &bytes[..])
+            .map_err(|_| supervisor_error("Failed to decode the balance"))
+    }
+}
+
+impl Externals for Runtime<'_> {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: RuntimeArgs,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        if index == PROFILE_FUNCTION_INDEX {
+            let function: u32 = args.nth_checked(0)?;
+            let instructions: u32 = args.nth_checked(1)?;
+            *self.profile.instructions.entry(function).or_default() += u64::from(instructions);
+            return Ok(None);
+        }
+
+        let name = HOST_FUNCTIONS[index];
+        log::trace!("{}({:?})", name, args);
+        *self.profile.host_calls.entry(name).or_default() += 1;
+        match name {
+            "seal_set_storage" => {
+                self.profile.storage_writes += 1;
+                let key = self.read_key(args.nth_checked(0)?)?;
+                let value = self.read(args.nth_checked(1)?, args.nth_checked(2)?)?;
+                self.storage.insert(key, value);
+                Ok(None)
+            }
+            "seal_clear_storage" => {
+                self.profile.storage_writes += 1;
+                let key = self.read_key(args.nth_checked(0)?)?;
+                self.storage.remove(&key);
+                Ok(None)
+            }
+            "seal_get_storage" => {
+                self.profile.storage_reads += 1;
+                let key = self.read_key(args.nth_checked(0)?)?;
+                match self.storage.get(&key) {
+                    Some(value) => {
+                        self.write_output(args.nth_checked(1)?, args.nth_checked(2)?, value)?;
+                        Ok(Some(ReturnCode::Success.into()))
+                    }
+                    None => Ok(Some(ReturnCode::KeyNotFound.into())),
+                }
+            }
+            "seal_transfer" => {
+                let value = self.read_balance(args.nth_checked(2)?, args.nth_checked(3)?)?;
+                if value > self.balance {
+                    return Ok(Some(ReturnCode::TransferFailed.into()));
+                }
+                self.balance -= value;
+                Ok(Some(ReturnCode::Success.into()))
+            }
+            "seal_terminate" => Err(TrapReason::Termination.into()),
+            "seal_input" => {
+                self.write_output(args.nth_checked(0)?, args.nth_checked(1)?, &self.input)?;
+                Ok(None)
+            }
+            "seal_return" => {
+                let flags = args.nth_checked(0)?;
+                let data = self.read(args.nth_checked(1)?, args.nth_checked(2)?)?;
+                Err(TrapReason::Return { flags, data }.into())
+            }
+            "seal_caller" => self.output_encoded(&args, 0, &self.sandbox.caller),
+            "seal_address" => self.output_encoded(&args, 0, &self.sandbox.address),
+            "seal_weight_to_fee" => {
+                let gas: u64 = args.nth_checked(0)?;
+                self.output_encoded(&args, 1, &Balance::from(gas))
+            }
+            "seal_gas_left" => self.output_encoded(&args, 0, &self.sandbox.gas_limit),
+            "seal_balance" => self.output_encoded(&args, 0, &self.balance),
+            "seal_value_transferred" => self.output_encoded(&args, 0, &self.value),
+            "seal_random" => {
+                let subject = self.read(args.nth_checked(0)?, args.nth_checked(1)?)?;
+                self.output_encoded(&args, 2, &blake2_256(&subject))
+            }
+            "seal_now" => self.output_encoded(&args, 0, &self.sandbox.timestamp),
+            "seal_minimum_balance" | "seal_tombstone_deposit" => {
+                self.output_encoded(&args, 0, &Balance::from(0u8))
+            }
+            "seal_deposit_event" => {
+                let topics = self.read(args.nth_checked(0)?, args.nth_checked(1)?)?;
+                let data = self.read(args.nth_checked(2)?, args.nth_checked(3)?)?;
+                self.events.push(Event { topics, data });
+                Ok(None)
+            }
+            "seal_set_rent_allowance" => Ok(None),
+            "seal_rent_allowance" => self.output_encoded(&args, 0, &Balance::MAX),
+            "seal_println" => {
+                let bytes = self.read(args.nth_checked(0)?, args.nth_checked(1)?)?;
+                self.debug_messages
+                    .push(String::from_utf8_lossy(&bytes).into_owned());
+                Ok(None)
+            }
+            "seal_block_number" => self.output_encoded(&args, 0, &self.sandbox.block_number),
+            "seal_hash_sha2_256" => {
+                self.output_hash(&args, |input| sha2::Sha256::digest(input).to_vec())
+            }
+            "seal_hash_keccak_256" => self.output_hash(&args, |input| keccak_256(input).to_vec()),
+            "seal_hash_blake2_256" => self.output_hash(&args, |input| blake2_256(input).to_vec()),
+            "seal_hash_blake2_128" => self.output_hash(&args, |input| blake2_128(input).to_vec()),
+            unsupported => Err(supervisor_error(format!(
+                "`{}` is not supported by the sandbox",
+                unsupported
+            ))),
+        }
+    }
+}
+
+impl Runtime<'_> {
+    /// Writes the SCALE encoded `value` to the output buffer passed as arguments starting at
+    /// `first_arg`.
+    fn output_encoded<E: Encode>(
+        &self,
+        args: &RuntimeArgs,
+        first_arg: usize,
+        value: &E,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let out_ptr = args.nth_checked(first_arg)?;
+        let out_len_ptr = args.nth_checked(first_arg + 1)?;
+        self.write_output(out_ptr, out_len_ptr, &value.encode())?;
+        Ok(None)
+    }
+
+    /// Hashes the input passed as the first two arguments and writes the digest to the output
+    /// pointer passed as third argument.
+    fn output_hash<F>(&self, args: &RuntimeArgs, hash: F) -> Result<Option<RuntimeValue>, Trap>
+    where
+        F: FnOnce(&[u8]) -> Vec<u8>,
+    {
+        let input = self.read(args.nth_checked(0)?, args.nth_checked(1)?)?;
+        self.write(args.nth_checked(2)?, &hash(&input))?;
+        Ok(None)
+    }
+}
+
+fn blake2_256(input: &[u8]) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    let mut blake2 = blake2::VarBlake2b::new(32).expect("32 is a valid output size");
+    blake2.update(input);
+    blake2.finalize_variable(|result| output.copy_from_slice(result));
+    output
+}
+
+fn blake2_128(input: &[u8]) -> [u8; 16] {
+    let mut output = [0u8; 16];
+    let mut blake2 = blake2::VarBlake2b::new(16).expect("16 is a valid output size");
+    blake2.update(input);
+    blake2.finalize_variable(|result| output.copy_from_slice(result));
+    output
+}
+
+fn keccak_256(input: &[u8]) -> [u8; 32] {
+    use tiny_keccak::Hasher as _;
+    let mut output = [0u8; 32];
+    let mut keccak = tiny_keccak::Keccak::v256();
+    keccak.update(input);
+    keccak.finalize(&mut output);
+    output
+}
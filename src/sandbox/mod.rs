@@ -0,0 +1,364 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+mod env;
+mod profile;
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use wasmi::{ImportsBuilder, ModuleInstance};
+
+use self::env::{Resolver, Runtime, TrapReason};
+pub use self::profile::{FunctionNames, Profile};
+
+/// The account id type of the sandbox, as used by the default ink! environment.
+pub type AccountId = [u8; 32];
+/// The balance type of the sandbox, as used by the default ink! environment.
+pub type Balance = u128;
+/// The contract storage of the sandbox, mapping storage keys to values.
+pub type Storage = HashMap<[u8; 32], Vec<u8>>;
+
+/// The account which executes the contract, unless configured otherwise.
+const DEFAULT_CALLER: AccountId = [1u8; 32];
+/// The account of the contract itself, unless configured otherwise.
+const DEFAULT_ADDRESS: AccountId = [2u8; 32];
+/// The gas limit of each execution, unless configured otherwise.
+const DEFAULT_GAS_LIMIT: u64 = 500_000_000;
+
+/// Set by the contract in `seal_return` to signal that the execution should be reverted.
+const FLAG_REVERT: u32 = 0x0000_0001;
+
+/// Executes the `deploy` and `call` exports of a contract Wasm blob locally.
+///
+/// The host functions of the `seal0` module are mocked, the contract storage is kept in memory
+/// and persists between executions. State changes of reverted or trapped executions are
+/// discarded.
+///
+/// Calling other contracts and instantiating contracts is not supported.
+pub struct Sandbox {
+    module: wasmi::Module,
+    profiling: bool,
+    function_names: FunctionNames,
+    storage: Storage,
+    caller: AccountId,
+    address: AccountId,
+    balance: Balance,
+    block_number: u32,
+    timestamp: u64,
+    gas_limit: u64,
+}
+
+/// An event deposited by the contract via `seal_deposit_event`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Event {
+    /// The SCALE encoded topics of the event.
+    pub topics: Vec<u8>,
+    /// The SCALE encoded event data.
+    pub data: Vec<u8>,
+}
+
+/// Result of executing a constructor or message in the sandbox.
+#[derive(Debug)]
+pub struct ExecResult {
+    /// The flags passed to `seal_return`, `0` if the contract did not call it.
+    pub flags: u32,
+    /// The output data passed to `seal_return`.
+    pub data: Vec<u8>,
+    /// The events deposited during the execution.
+    pub events: Vec<Event>,
+    /// The messages printed via `seal_println` during the execution.
+    pub debug_messages: Vec<String>,
+    /// The execution statistics, instruction counts are only collected in profiling mode.
+    pub profile: Profile,
+}
+
+impl ExecResult {
+    /// Returns `true` if the contract requested its state changes to be reverted.
+    pub fn did_revert(&self) -> bool {
+        self.flags & FLAG_REVERT != 0
+    }
+}
+
+impl Sandbox {
+    /// Creates a new sandbox for the given contract Wasm blob, with empty storage.
+    pub fn new(wasm: &[u8]) -> Result<Self> {
+        let module = wasmi::Module::from_buffer(wasm)
+            .map_err(|err| anyhow::anyhow!("Loading the contract Wasm failed: {}", err))?;
+        Ok(Self::with_module(module, false, Default::default()))
+    }
+
+    /// Creates a new sandbox which counts the instructions executed by every function.
+    ///
+    /// Function names are taken from the `name` section of the Wasm blob, if it has one.
+    pub fn new_profiled(wasm: &[u8]) -> Result<Self> {
+        let module: parity_wasm::elements::Module =
+            parity_wasm::deserialize_buffer(wasm).context("Loading the contract Wasm failed")?;
+        let function_names = FunctionNames::from_module(&module);
+        let module = wasmi::Module::from_parity_wasm_module(profile::instrument(module)?)
+            .map_err(|err| anyhow::anyhow!("Loading the instrumented Wasm failed: {}", err))?;
+        Ok(Self::with_module(module, true, function_names))
+    }
+
+    fn with_module(module: wasmi::Module, profiling: bool, function_names: FunctionNames) -> Self {
+        Sandbox {
+            module,
+            profiling,
+            function_names,
+            storage: Default::default(),
+            caller: DEFAULT_CALLER,
+            address: DEFAULT_ADDRESS,
+            balance: 0,
+            block_number: 0,
+            timestamp: 0,
+            gas_limit: DEFAULT_GAS_LIMIT,
+        }
+    }
+
+    /// Returns the function names of the contract, only available in profiling mode.
+    pub fn function_names(&self) -> &FunctionNames {
+        &self.function_names
+    }
+
+    /// Names the contract functions, for a Wasm without a `name` section.
+    pub fn set_function_names(&mut self, function_names: FunctionNames) {
+        self.function_names = function_names;
+    }
+
+    /// Returns the current contract storage.
+    #[cfg(test)]
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    /// Executes the contract's `deploy` export with the given input, i.e. runs a constructor.
+    pub fn deploy(&mut self, input: &[u8], value: Balance) -> Result<ExecResult> {
+        self.execute("deploy", input, value)
+    }
+
+    /// Executes the contract's `call` export with the given input, i.e. runs a message.
+    pub fn call(&mut self, input: &[u8], value: Balance) -> Result<ExecResult> {
+        self.execute("call", input, value)
+    }
+
+    /// Instantiates a fresh module instance and invokes the given export.
+    ///
+    /// The storage is only updated if the execution succeeded and was not reverted.
+    fn execute(&mut self, export: &str, input: &[u8], value: Balance) -> Result<ExecResult> {
+        let resolver = Resolver::new(self.profiling);
+        let imports = ImportsBuilder::new()
+            .with_resolver("seal0", &resolver)
+            .with_resolver("env", &resolver);
+        let instance = ModuleInstance::new(&self.module, &imports)
+            .map_err(|err| anyhow::anyhow!("Instantiating the contract failed: {}", err))?;
+        if instance.has_start() {
+            anyhow::bail!("Contracts must not have a start function")
+        }
+        let instance = instance.assert_no_start();
+        let memory = resolver.memory().context(
+            "Memory import is not found. Is --import-memory specified in the linker args",
+        )?;
+
+        let mut runtime = Runtime::new(self, memory, input.to_vec(), value);
+        let outcome = instance.invoke_export(export, &[], &mut runtime);
+        let (storage, balance, events, debug_messages, profile) = runtime.into_parts();
+
+        for message in &debug_messages {
+            log::debug!("seal_println: {}", message);
+        }
+
+        let (flags, data, terminated) = match outcome {
+            Ok(_) => (0, Vec::new(), false),
+            Err(err) => match err
+                .as_host_error()
+                .and_then(|err| err.downcast_ref::<TrapReason>())
+            {
+                Some(TrapReason::Return { flags, data }) => (*flags, data.clone(), false),
+                Some(TrapReason::Termination) => (0, Vec::new(), true),
+                Some(TrapReason::SupervisorError(msg)) => {
+                    anyhow::bail!("Contract execution failed: {}", msg)
+                }
+                None => anyhow::bail!("Contract trapped: {}", err),
+            },
+        };
+
+        let result = ExecResult {
+            flags,
+            data,
+            events,
+            debug_messages,
+            profile,
+        };
+        if !result.did_revert() {
+            if terminated {
+                self.storage.clear();
+                self.balance = 0;
+            } else {
+                self.storage = storage;
+                self.balance = balance;
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use pretty_assertions::assert_eq;
+
+    /// Stores the input under the key `[0; 32]` on `deploy`, returns the stored value on `call`.
+    const STORE_AND_LOAD: &str = r#"
+(module
+    (import "seal0" "seal_input" (func $seal_input (param i32 i32)))
+    (import "seal0" "seal_set_storage" (func $seal_set_storage (param i32 i32 i32)))
+    (import "seal0" "seal_get_storage" (func $seal_get_storage (param i32 i32 i32) (result i32)))
+    (import "seal0" "seal_return" (func $seal_return (param i32 i32 i32)))
+    (import "env" "memory" (memory 1 1))
+
+    ;; [0, 32) storage key
+    ;; [32, 36) buffer length
+    (data (i32.const 32) "\40")
+    ;; [64, 128) buffer
+
+    (func (export "deploy")
+        (call $seal_input (i32.const 64) (i32.const 32))
+        (call $seal_set_storage (i32.const 0) (i32.const 64) (i32.load (i32.const 32)))
+    )
+    (func (export "call")
+        (if (call $seal_get_storage (i32.const 0) (i32.const 64) (i32.const 32))
+            (then (unreachable))
+        )
+        (call $seal_return (i32.const 0) (i32.const 64) (i32.load (i32.const 32)))
+    )
+)
+"#;
+
+    /// Writes to storage and then reverts.
+    const REVERT: &str = r#"
+(module
+    (import "seal0" "seal_set_storage" (func $seal_set_storage (param i32 i32 i32)))
+    (import "seal0" "seal_return" (func $seal_return (param i32 i32 i32)))
+    (import "env" "memory" (memory 1 1))
+
+    (func (export "deploy"))
+    (func (export "call")
+        (call $seal_set_storage (i32.const 0) (i32.const 0) (i32.const 4))
+        (call $seal_return (i32.const 1) (i32.const 0) (i32.const 4))
+    )
+)
+"#;
+
+    #[test]
+    fn storage_persists_between_executions() {
+        let wasm = wabt::wat2wasm(STORE_AND_LOAD).expect("invalid wabt");
+        let mut sandbox = Sandbox::new(&wasm).unwrap();
+
+        let deployed = sandbox.deploy(&[1, 2, 3, 4], 0).unwrap();
+        let called = sandbox.call(&[], 0).unwrap();
+
+        assert!(!deployed.did_revert());
+        assert_eq!(sandbox.storage().get(&[0u8; 32]), Some(&vec![1, 2, 3, 4]));
+        assert_eq!(called.flags, 0);
+        assert_eq!(called.data, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn reverted_execution_discards_state_changes() {
+        let wasm = wabt::wat2wasm(REVERT).expect("invalid wabt");
+        let mut sandbox = Sandbox::new(&wasm).unwrap();
+
+        sandbox.deploy(&[], 0).unwrap();
+        let result = sandbox.call(&[], 0).unwrap();
+
+        assert!(result.did_revert());
+        assert!(sandbox.storage().is_empty());
+    }
+
+    #[test]
+    fn trap_is_reported_as_error() {
+        let wasm = wabt::wat2wasm(STORE_AND_LOAD).expect("invalid wabt");
+        let mut sandbox = Sandbox::new(&wasm).unwrap();
+
+        // nothing stored yet, so `seal_get_storage` fails and the contract traps
+        let result = sandbox.call(&[], 0);
+
+        assert_matches!(result, Err(err) if err.to_string().starts_with("Contract trapped"));
+    }
+
+    #[test]
+    fn unknown_host_function_is_rejected() {
+        let wasm = wabt::wat2wasm(
+            r#"(module (import "seal0" "seal_unknown" (func)) (import "env" "memory" (memory 1)))"#,
+        )
+        .expect("invalid wabt");
+        let mut sandbox = Sandbox::new(&wasm).unwrap();
+
+        let result = sandbox.deploy(&[], 0);
+
+        assert_matches!(result, Err(err) if err.to_string().contains("seal_unknown"));
+    }
+
+    #[test]
+    fn host_calls_and_storage_accesses_are_counted() {
+        let wasm = wabt::wat2wasm(STORE_AND_LOAD).expect("invalid wabt");
+        let mut sandbox = Sandbox::new(&wasm).unwrap();
+
+        let result = sandbox.deploy(&[1, 2, 3, 4], 0).unwrap();
+
+        assert_eq!(result.profile.host_calls.get("seal_input"), Some(&1));
+        assert_eq!(result.profile.host_calls.get("seal_set_storage"), Some(&1));
+        assert_eq!(result.profile.storage_reads, 0);
+        assert_eq!(result.profile.storage_writes, 1);
+        // instructions are only counted in profiling mode
+        assert_eq!(result.profile.total_instructions(), 0);
+    }
+
+    #[test]
+    fn profiled_execution_counts_instructions_per_function() {
+        let wasm = wabt::Wat2Wasm::new()
+            .write_debug_names(true)
+            .convert(
+                r#"
+(module
+    (import "env" "memory" (memory 1 1))
+    (func $helper (result i32)
+        (i32.add (i32.const 1) (i32.const 2))
+    )
+    (func (export "deploy")
+        (drop (call $helper))
+        (drop (call $helper))
+    )
+    (func (export "call"))
+)
+"#,
+            )
+            .expect("invalid wabt");
+        let mut sandbox = Sandbox::new_profiled(wasm.as_ref()).unwrap();
+
+        let result = sandbox.deploy(&[], 0).unwrap();
+        let hot_functions = result.profile.hot_functions(sandbox.function_names(), 10);
+
+        assert_eq!(hot_functions.len(), 2);
+        assert_eq!(hot_functions[0].0, "helper");
+        assert_eq!(hot_functions[0].1 % 2, 0, "helper is executed twice");
+        assert_eq!(hot_functions[1].0, "func[1]");
+        assert_eq!(
+            result.profile.total_instructions(),
+            hot_functions[0].1 + hot_functions[1].1
+        );
+    }
+}
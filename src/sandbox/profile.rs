@@ -0,0 +1,168 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    iter::FromIterator,
+};
+
+use anyhow::Result;
+use parity_wasm::elements::{
+    External, FunctionType, ImportCountType, Instruction, Module, Type, ValueType,
+};
+
+/// The host function imported from the `env` module by instrumented contracts.
+///
+/// It is called at the start of every metered block with the index of the enclosing function
+/// and the number of instructions in the block.
+pub(super) const PROFILE_FUNCTION: &str = "gas";
+
+/// Names of the contract functions by function index, taken from the `name` section.
+#[derive(Debug, Default)]
+pub struct FunctionNames(HashMap<u32, String>);
+
+impl FunctionNames {
+    /// Collects the demangled function names from the `name` section of the module.
+    ///
+    /// Returns no names if the module has no `name` section.
+    pub fn from_module(module: &Module) -> Self {
+        let module = module
+            .clone()
+            .parse_names()
+            .unwrap_or_else(|(_, module)| module);
+        let names = module
+            .names_section()
+            .and_then(|section| section.functions())
+            .map(|functions| {
+                functions
+                    .names()
+                    .iter()
+                    .map(|(index, name)| (index, format!("{:#}", rustc_demangle::demangle(name))))
+                    .collect()
+            })
+            .unwrap_or_default();
+        FunctionNames(names)
+    }
+
+    /// Returns `true` if no function names are known.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the known function names with their indices, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.0.iter().map(|(index, name)| (*index, name.as_str()))
+    }
+
+    /// Returns the name of the function, or a placeholder with its index if it is unknown.
+    pub fn name(&self, index: u32) -> String {
+        self.0
+            .get(&index)
+            .cloned()
+            .unwrap_or_else(|| format!("func[{}]", index))
+    }
+}
+
+impl FromIterator<(u32, String)> for FunctionNames {
+    fn from_iter<I: IntoIterator<Item = (u32, String)>>(iter: I) -> Self {
+        FunctionNames(iter.into_iter().collect())
+    }
+}
+
+/// Execution statistics of a single constructor or message execution.
+#[derive(Debug, Default)]
+pub struct Profile {
+    /// Executed instructions by function index, only collected for instrumented modules.
+    pub instructions: HashMap<u32, u64>,
+    /// Number of calls by host function name.
+    pub host_calls: BTreeMap<&'static str, u64>,
+    /// Number of storage reads, i.e. calls to `seal_get_storage`.
+    pub storage_reads: u64,
+    /// Number of storage writes, i.e. calls to `seal_set_storage` and `seal_clear_storage`.
+    pub storage_writes: u64,
+}
+
+impl Profile {
+    /// Returns the total number of executed instructions.
+    pub fn total_instructions(&self) -> u64 {
+        self.instructions.values().sum()
+    }
+
+    /// Returns the `n` functions which executed the most instructions, in descending order.
+    pub fn hot_functions(&self, names: &FunctionNames, n: usize) -> Vec<(String, u64)> {
+        let mut functions = self.instructions.iter().collect::<Vec<_>>();
+        functions.sort_by(|(a_idx, a), (b_idx, b)| b.cmp(a).then(a_idx.cmp(b_idx)));
+        functions
+            .into_iter()
+            .take(n)
+            .map(|(index, count)| (names.name(*index), *count))
+            .collect()
+    }
+}
+
+/// Instruments the module to report the number of instructions executed by every function.
+///
+/// Uses the gas metering of `pwasm_utils`, with a cost of `1` for every instruction, and extends
+/// every call of the injected `gas` import by the index of the enclosing function. The reported
+/// function indices are the ones of the original module.
+pub(super) fn instrument(module: Module) -> Result<Module> {
+    let imported_functions = module.import_count(ImportCountType::Function) as u32;
+    let rules = pwasm_utils::rules::Set::default();
+    let mut module = pwasm_utils::inject_gas_counter(module, &rules, "env")
+        .map_err(|_| anyhow::anyhow!("Instrumenting the Wasm for profiling failed"))?;
+    // the `gas` import is appended to the imported functions
+    let profile_function = imported_functions;
+
+    let types = module
+        .type_section_mut()
+        .expect("a type section was added for the gas import; qed")
+        .types_mut();
+    types.push(Type::Function(FunctionType::new(
+        vec![ValueType::I32, ValueType::I32],
+        vec![],
+    )));
+    let profile_type = types.len() as u32 - 1;
+
+    let profile_import = module
+        .import_section_mut()
+        .expect("an import section was added for the gas import; qed")
+        .entries_mut()
+        .iter_mut()
+        .find(|entry| entry.module() == "env" && entry.field() == PROFILE_FUNCTION)
+        .expect("the gas import was added; qed");
+    *profile_import.external_mut() = External::Function(profile_type);
+
+    if let Some(code) = module.code_section_mut() {
+        for (i, body) in code.bodies_mut().iter_mut().enumerate() {
+            let function_index = imported_functions + i as u32;
+            let instructions = body.code_mut().elements_mut();
+            let mut instrumented = Vec::with_capacity(instructions.len());
+            for instruction in instructions.drain(..) {
+                if instruction == Instruction::Call(profile_function) {
+                    // the block cost was pushed by the previous instruction
+                    let cost = instrumented
+                        .pop()
+                        .expect("the cost precedes the gas call; qed");
+                    instrumented.push(Instruction::I32Const(function_index as i32));
+                    instrumented.push(cost);
+                }
+                instrumented.push(instruction);
+            }
+            *instructions = instrumented;
+        }
+    }
+    Ok(module)
+}
@@ -0,0 +1,149 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::TryFrom;
+
+use anyhow::Result;
+use codec::{Compact, Decode};
+use serde_json::{Map, Value};
+
+use super::{Field, InkProject, Primitive, TypeDef};
+
+/// Decodes a SCALE encoded value of the type with the given id into JSON.
+///
+/// The JSON has the form accepted by [`super::encode::encode_value`]: integers beyond `u64` are
+/// strings, sequences and arrays of bytes hex strings and composites with a single unnamed field
+/// are represented by their field. Named fields are always decoded into an object.
+pub(super) fn decode_value(project: &InkProject, ty: u32, input: &mut &[u8]) -> Result<Value> {
+    match &project.resolve(ty)?.def {
+        TypeDef::Composite { fields } => decode_fields(project, fields, input),
+        TypeDef::Variant { variants } => {
+            let index = u8::decode(input)?;
+            let (_, variant) = variants
+                .iter()
+                .enumerate()
+                .find(|(i, variant)| variant.discriminant.unwrap_or(*i as u64) == index as u64)
+                .ok_or_else(|| anyhow::anyhow!("Invalid variant index {}", index))?;
+            if variant.fields.is_empty() {
+                return Ok(Value::String(variant.name.clone()));
+            }
+            let fields = decode_fields(project, &variant.fields, input)?;
+            let mut object = Map::new();
+            object.insert(variant.name.clone(), fields);
+            Ok(Value::Object(object))
+        }
+        TypeDef::Sequence { ty } => {
+            let len = <Compact<u32>>::decode(input)?.0 as usize;
+            decode_elements(project, *ty, len, input)
+        }
+        TypeDef::Array { len, ty } => decode_elements(project, *ty, *len as usize, input),
+        TypeDef::Tuple(types) => {
+            if types.is_empty() {
+                return Ok(Value::Null);
+            }
+            let elements = types
+                .iter()
+                .map(|ty| decode_value(project, *ty, input))
+                .collect::<Result<_>>()?;
+            Ok(Value::Array(elements))
+        }
+        TypeDef::Primitive(primitive) => decode_primitive(*primitive, input),
+        TypeDef::Compact { .. } => Ok(integer(<Compact<u128>>::decode(input)?.0)),
+        TypeDef::Phantom {} => Ok(Value::Null),
+    }
+}
+
+/// Decodes the fields of a composite or variant.
+///
+/// Named fields are decoded into an object, unnamed ones into an array.
+fn decode_fields(project: &InkProject, fields: &[Field], input: &mut &[u8]) -> Result<Value> {
+    match fields {
+        [] => Ok(Value::Null),
+        [field] if field.name.is_none() => decode_value(project, field.ty, input),
+        fields if fields.iter().all(|field| field.name.is_some()) => {
+            let mut object = Map::new();
+            for field in fields {
+                let name = field.name.clone().expect("all fields are named; qed");
+                object.insert(name, decode_value(project, field.ty, input)?);
+            }
+            Ok(Value::Object(object))
+        }
+        fields => {
+            let elements = fields
+                .iter()
+                .map(|field| decode_value(project, field.ty, input))
+                .collect::<Result<_>>()?;
+            Ok(Value::Array(elements))
+        }
+    }
+}
+
+/// Decodes the elements of a sequence or array, bytes are decoded into a hex string.
+fn decode_elements(project: &InkProject, ty: u32, len: usize, input: &mut &[u8]) -> Result<Value> {
+    if let TypeDef::Primitive(Primitive::U8) = project.resolve(ty)?.def {
+        if input.len() < len {
+            anyhow::bail!("Expected {} bytes, got {}", len, input.len());
+        }
+        let (bytes, rest) = input.split_at(len);
+        *input = rest;
+        return Ok(Value::String(format!("0x{}", hex::encode(bytes))));
+    }
+    let elements = (0..len)
+        .map(|_| decode_value(project, ty, input))
+        .collect::<Result<_>>()?;
+    Ok(Value::Array(elements))
+}
+
+fn decode_primitive(primitive: Primitive, input: &mut &[u8]) -> Result<Value> {
+    let value = match primitive {
+        Primitive::Bool => Value::Bool(bool::decode(input)?),
+        Primitive::Char => {
+            let c = u32::decode(input)?;
+            let c =
+                std::char::from_u32(c).ok_or_else(|| anyhow::anyhow!("Invalid char {:#x}", c))?;
+            Value::String(c.to_string())
+        }
+        Primitive::Str => Value::String(String::decode(input)?),
+        Primitive::U8 => u8::decode(input)?.into(),
+        Primitive::U16 => u16::decode(input)?.into(),
+        Primitive::U32 => u32::decode(input)?.into(),
+        Primitive::U64 => u64::decode(input)?.into(),
+        Primitive::U128 => integer(u128::decode(input)?),
+        Primitive::I8 => i8::decode(input)?.into(),
+        Primitive::I16 => i16::decode(input)?.into(),
+        Primitive::I32 => i32::decode(input)?.into(),
+        Primitive::I64 => i64::decode(input)?.into(),
+        Primitive::I128 => {
+            let i = i128::decode(input)?;
+            match i64::try_from(i) {
+                Ok(i) => i.into(),
+                Err(_) => Value::String(i.to_string()),
+            }
+        }
+        Primitive::U256 | Primitive::I256 => {
+            anyhow::bail!("256 bit integers are not supported")
+        }
+    };
+    Ok(value)
+}
+
+/// Returns integers beyond the range of JSON numbers as strings.
+fn integer(i: u128) -> Value {
+    match u64::try_from(i) {
+        Ok(i) => i.into(),
+        Err(_) => Value::String(i.to_string()),
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{Context, Result};
+use codec::{Compact, Encode};
+use serde_json::Value;
+
+use super::{decode_hex, Field, InkProject, Primitive, TypeDef};
+
+/// SCALE encodes the JSON value as the type with the given id.
+///
+/// - Integers are given as JSON numbers or strings, the latter allow for values beyond `u64`.
+/// - Sequences and arrays of bytes can be given as hex strings.
+/// - Composites with a single field are encoded like their field.
+/// - Variants without fields are given by name, variants with fields as an object with the
+///   name as the only key.
+pub(super) fn encode_value(
+    project: &InkProject,
+    ty: u32,
+    value: &Value,
+    output: &mut Vec<u8>,
+) -> Result<()> {
+    match &project.resolve(ty)?.def {
+        TypeDef::Composite { fields } => encode_fields(project, fields, value, output),
+        TypeDef::Variant { variants } => {
+            let (name, fields_value) = match value {
+                Value::String(name) => (name, &Value::Null),
+                Value::Object(object) if object.len() == 1 => {
+                    object.iter().next().expect("object has one entry; qed")
+                }
+                _ => anyhow::bail!("Expected a variant name or {{\"<variant>\": <fields>}}"),
+            };
+            let (index, variant) = variants
+                .iter()
+                .enumerate()
+                .find(|(_, variant)| &variant.name == name)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown variant '{}', expected one of: {}",
+                        name,
+                        variants
+                            .iter()
+                            .map(|variant| variant.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })?;
+            let index = variant.discriminant.unwrap_or(index as u64) as u8;
+            index.encode_to(output);
+            encode_fields(project, &variant.fields, fields_value, output)
+        }
+        TypeDef::Sequence { ty } => {
+            if let Some(bytes) = as_bytes(project, *ty, value)? {
+                bytes.encode_to(output);
+                return Ok(());
+            }
+            let elements = as_array(value)?;
+            Compact(elements.len() as u32).encode_to(output);
+            for element in elements {
+                encode_value(project, *ty, element, output)?;
+            }
+            Ok(())
+        }
+        TypeDef::Array { len, ty } => {
+            let bytes = as_bytes(project, *ty, value)?;
+            let elements = match bytes {
+                Some(ref bytes) => bytes.iter().map(|byte| Value::from(*byte)).collect(),
+                None => as_array(value)?.clone(),
+            };
+            if elements.len() != *len as usize {
+                anyhow::bail!("Expected {} elements, got {}", len, elements.len());
+            }
+            for element in &elements {
+                encode_value(project, *ty, element, output)?;
+            }
+            Ok(())
+        }
+        TypeDef::Tuple(types) => {
+            if types.is_empty() {
+                return Ok(());
+            }
+            let elements = as_array(value)?;
+            if elements.len() != types.len() {
+                anyhow::bail!(
+                    "Expected a tuple of {} elements, got {}",
+                    types.len(),
+                    elements.len()
+                );
+            }
+            for (ty, element) in types.iter().zip(elements) {
+                encode_value(project, *ty, element, output)?;
+            }
+            Ok(())
+        }
+        TypeDef::Primitive(primitive) => encode_primitive(*primitive, value, output),
+        TypeDef::Compact { ty } => {
+            match project.resolve(*ty)?.def {
+                TypeDef::Primitive(Primitive::U8)
+                | TypeDef::Primitive(Primitive::U16)
+                | TypeDef::Primitive(Primitive::U32)
+                | TypeDef::Primitive(Primitive::U64)
+                | TypeDef::Primitive(Primitive::U128) => {
+                    // the compact encoding only depends on the value, not on the integer type
+                    Compact(parse_int::<u128>(value)?).encode_to(output)
+                }
+                _ => anyhow::bail!("Only unsigned integers can be compact encoded"),
+            }
+            Ok(())
+        }
+        TypeDef::Phantom {} => Ok(()),
+    }
+}
+
+/// Encodes the fields of a composite or variant.
+///
+/// Named fields can be given as an object, all fields as an array in declaration order.
+fn encode_fields(
+    project: &InkProject,
+    fields: &[Field],
+    value: &Value,
+    output: &mut Vec<u8>,
+) -> Result<()> {
+    match (fields, value) {
+        ([], Value::Null) => Ok(()),
+        ([], _) => anyhow::bail!("Expected no fields"),
+        ([field], value) if !value.is_object() || field.name.is_none() => {
+            encode_value(project, field.ty, value, output)
+        }
+        (fields, Value::Object(object)) => {
+            for field in fields {
+                let name = field
+                    .name
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("Expected the unnamed fields as array"))?;
+                let value = object
+                    .get(name)
+                    .ok_or_else(|| anyhow::anyhow!("Missing field `{}`", name))?;
+                encode_value(project, field.ty, value, output)
+                    .context(format!("Invalid field `{}`", name))?;
+            }
+            Ok(())
+        }
+        (fields, Value::Array(elements)) if elements.len() == fields.len() => {
+            for (field, element) in fields.iter().zip(elements) {
+                encode_value(project, field.ty, element, output)?;
+            }
+            Ok(())
+        }
+        (fields, _) => anyhow::bail!("Expected an object or array of {} fields", fields.len()),
+    }
+}
+
+fn encode_primitive(primitive: Primitive, value: &Value, output: &mut Vec<u8>) -> Result<()> {
+    match primitive {
+        Primitive::Bool => match value {
+            Value::Bool(b) => b.encode_to(output),
+            _ => anyhow::bail!("Expected a bool, got {}", value),
+        },
+        Primitive::Char => {
+            let s = as_str(value)?;
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => (c as u32).encode_to(output),
+                _ => anyhow::bail!("Expected a single char, got '{}'", s),
+            }
+        }
+        Primitive::Str => as_str(value)?.encode_to(output),
+        Primitive::U8 => parse_int::<u8>(value)?.encode_to(output),
+        Primitive::U16 => parse_int::<u16>(value)?.encode_to(output),
+        Primitive::U32 => parse_int::<u32>(value)?.encode_to(output),
+        Primitive::U64 => parse_int::<u64>(value)?.encode_to(output),
+        Primitive::U128 => parse_int::<u128>(value)?.encode_to(output),
+        Primitive::I8 => parse_int::<i8>(value)?.encode_to(output),
+        Primitive::I16 => parse_int::<i16>(value)?.encode_to(output),
+        Primitive::I32 => parse_int::<i32>(value)?.encode_to(output),
+        Primitive::I64 => parse_int::<i64>(value)?.encode_to(output),
+        Primitive::I128 => parse_int::<i128>(value)?.encode_to(output),
+        Primitive::U256 | Primitive::I256 => {
+            anyhow::bail!("256 bit integers are not supported")
+        }
+    }
+    Ok(())
+}
+
+/// Returns the bytes of a hex string, if the elements of the sequence or array are bytes.
+fn as_bytes(project: &InkProject, element_ty: u32, value: &Value) -> Result<Option<Vec<u8>>> {
+    match (&project.resolve(element_ty)?.def, value) {
+        (TypeDef::Primitive(Primitive::U8), Value::String(s)) => decode_hex(s)
+            .map(Some)
+            .context(format!("Expected a hex string, got '{}'", s)),
+        _ => Ok(None),
+    }
+}
+
+fn as_array(value: &Value) -> Result<&Vec<Value>> {
+    value
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Expected an array, got {}", value))
+}
+
+/// Returns the string value, numbers and bools are taken by their textual representation.
+fn as_str(value: &Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => anyhow::bail!("Expected a string, got {}", value),
+    }
+}
+
+fn parse_int<T>(value: &Value) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let s = match value {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        _ => anyhow::bail!("Expected an integer, got {}", value),
+    };
+    s.parse::<T>()
+        .map_err(|err| anyhow::anyhow!("Invalid integer '{}': {}", s, err))
+}
@@ -0,0 +1,531 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+//! SCALE encoding of contract constructor and message calls and decoding of contract events and
+//! return values, driven by the ink! metadata.
+//!
+//! The `spec` section of the `metadata.json` generated by `cargo contract build` provides the
+//! selectors and argument types of the constructors and messages and the events of the
+//! contract, the `types` section the type registry the argument types refer to.
+
+mod decode;
+mod encode;
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{crate_metadata::CrateMetadata, workspace::ManifestPath};
+
+/// The subset of the ink! metadata required to encode calls and decode their results.
+#[derive(Debug, Deserialize)]
+pub struct InkProject {
+    spec: ContractSpec,
+    types: Vec<Type>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContractSpec {
+    constructors: Vec<MessageSpec>,
+    messages: Vec<MessageSpec>,
+    events: Vec<EventSpec>,
+}
+
+/// A constructor or message of the contract.
+#[derive(Debug, Deserialize)]
+pub struct MessageSpec {
+    name: Vec<String>,
+    selector: String,
+    args: Vec<MessageParam>,
+    #[serde(rename = "returnType")]
+    return_type: Option<TypeSpec>,
+}
+
+impl MessageSpec {
+    /// Returns the name of the constructor or message, trait messages are prefixed with the
+    /// name of the trait.
+    pub fn name(&self) -> String {
+        self.name.join("::")
+    }
+}
+
+/// An event the contract can emit.
+#[derive(Debug, Deserialize)]
+struct EventSpec {
+    name: String,
+    args: Vec<MessageParam>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageParam {
+    name: String,
+    #[serde(rename = "type")]
+    ty: TypeSpec,
+}
+
+#[derive(Debug, Deserialize)]
+struct TypeSpec {
+    #[serde(rename = "type")]
+    id: u32,
+    #[serde(rename = "displayName")]
+    display_name: Vec<String>,
+}
+
+/// A type of the type registry.
+#[derive(Debug, Deserialize)]
+struct Type {
+    def: TypeDef,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TypeDef {
+    Composite {
+        #[serde(default)]
+        fields: Vec<Field>,
+    },
+    Variant {
+        #[serde(default)]
+        variants: Vec<Variant>,
+    },
+    Sequence {
+        #[serde(rename = "type")]
+        ty: u32,
+    },
+    Array {
+        len: u32,
+        #[serde(rename = "type")]
+        ty: u32,
+    },
+    Tuple(Vec<u32>),
+    Primitive(Primitive),
+    Compact {
+        #[serde(rename = "type")]
+        ty: u32,
+    },
+    Phantom {},
+}
+
+#[derive(Debug, Deserialize)]
+struct Field {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    ty: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Variant {
+    name: String,
+    #[serde(default)]
+    fields: Vec<Field>,
+    discriminant: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Primitive {
+    Bool,
+    Char,
+    Str,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    U256,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    I256,
+}
+
+impl InkProject {
+    /// Loads the ink! metadata from a `metadata.json` or `<name>.contract` file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            fs::read(path).context(format!("Failed to read metadata {}", path.display()))?;
+        serde_json::from_slice(&contents)
+            .context(format!("Failed to parse metadata {}", path.display()))
+    }
+
+    /// Loads the ink! metadata generated by `cargo contract build` for the contract in the
+    /// current directory.
+    pub fn load_default() -> Result<Self> {
+        let crate_metadata = CrateMetadata::collect(&ManifestPath::discover()?)?;
+        Self::load(&crate_metadata.artifact_directory.join("metadata.json"))
+    }
+
+    /// Encodes a call of the named constructor with the given arguments.
+    pub fn encode_constructor<S: AsRef<str>>(&self, name: &str, args: &[S]) -> Result<Vec<u8>> {
+        let constructor = find_message(&self.spec.constructors, name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "No constructor named '{}', available constructors: {}",
+                name,
+                message_names(&self.spec.constructors)
+            )
+        })?;
+        self.encode_call(constructor, args)
+    }
+
+    /// Encodes a call of the named message with the given arguments.
+    pub fn encode_message<S: AsRef<str>>(&self, name: &str, args: &[S]) -> Result<Vec<u8>> {
+        let message = find_message(&self.spec.messages, name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "No message named '{}', available messages: {}",
+                name,
+                message_names(&self.spec.messages)
+            )
+        })?;
+        self.encode_call(message, args)
+    }
+
+    /// Encodes the selector followed by every argument.
+    ///
+    /// Each argument is parsed as JSON, arguments which are no valid JSON are taken as strings.
+    /// Numbers beyond the 64 bit range are kept as strings, as JSON numbers would lose precision.
+    fn encode_call<S: AsRef<str>>(&self, spec: &MessageSpec, args: &[S]) -> Result<Vec<u8>> {
+        if spec.args.len() != args.len() {
+            anyhow::bail!(
+                "'{}' expects {} arguments ({}), but {} were supplied",
+                spec.name(),
+                spec.args.len(),
+                spec.args
+                    .iter()
+                    .map(|arg| format!("{}: {}", arg.name, arg.ty.display_name.join("::")))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                args.len()
+            );
+        }
+        let mut output =
+            decode_hex(&spec.selector).context(format!("Invalid selector of '{}'", spec.name()))?;
+        for (param, arg) in spec.args.iter().zip(args) {
+            let arg = arg.as_ref();
+            let value = match serde_json::from_str(arg) {
+                Ok(Value::Number(n)) if n.is_f64() => Value::String(arg.to_string()),
+                Ok(value) => value,
+                Err(_) => Value::String(arg.to_string()),
+            };
+            encode::encode_value(self, param.ty.id, &value, &mut output)
+                .context(format!("Invalid argument `{}`", param.name))?;
+        }
+        Ok(output)
+    }
+
+    /// Decodes an event emitted by the contract.
+    ///
+    /// Returns an object with the event name as the only key and the event arguments by name.
+    pub fn decode_event(&self, data: &[u8]) -> Result<Value> {
+        let mut input = data;
+        // the events of the contract are encoded as variants of a single enum
+        let index = *input
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Expected an event, got no data"))?;
+        input = &input[1..];
+        let event = self
+            .spec
+            .events
+            .get(index as usize)
+            .ok_or_else(|| anyhow::anyhow!("Invalid event index {}", index))?;
+        let mut args = Map::new();
+        for arg in &event.args {
+            let value = decode::decode_value(self, arg.ty.id, &mut input).context(format!(
+                "Invalid argument `{}` of event {}",
+                arg.name, event.name
+            ))?;
+            args.insert(arg.name.clone(), value);
+        }
+        ensure_consumed(input)?;
+        let mut decoded = Map::new();
+        decoded.insert(event.name.clone(), Value::Object(args));
+        Ok(Value::Object(decoded))
+    }
+
+    /// Decodes the value returned by the named message.
+    ///
+    /// Returns `null` if the message has no return type.
+    pub fn decode_return(&self, name: &str, data: &[u8]) -> Result<Value> {
+        let message = find_message(&self.spec.messages, name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "No message named '{}', available messages: {}",
+                name,
+                message_names(&self.spec.messages)
+            )
+        })?;
+        let return_type = match message.return_type {
+            Some(ref return_type) => return_type,
+            None => return Ok(Value::Null),
+        };
+        let mut input = data;
+        let value = decode::decode_value(self, return_type.id, &mut input)
+            .context(format!("Invalid return value of '{}'", message.name()))?;
+        ensure_consumed(input)?;
+        Ok(value)
+    }
+
+    /// Returns the type with the given id from the type registry.
+    fn resolve(&self, id: u32) -> Result<&Type> {
+        // type ids are 1-based
+        id.checked_sub(1)
+            .and_then(|index| self.types.get(index as usize))
+            .ok_or_else(|| anyhow::anyhow!("Type {} not found in the type registry", id))
+    }
+}
+
+/// Finds a constructor or message by its full name or by its name without the trait prefix.
+fn find_message<'a>(messages: &'a [MessageSpec], name: &str) -> Option<&'a MessageSpec> {
+    messages
+        .iter()
+        .find(|message| message.name() == name)
+        .or_else(|| {
+            messages
+                .iter()
+                .find(|message| message.name.last().map(String::as_str) == Some(name))
+        })
+}
+
+fn message_names(messages: &[MessageSpec]) -> String {
+    messages
+        .iter()
+        .map(MessageSpec::name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn ensure_consumed(input: &[u8]) -> Result<()> {
+    if !input.is_empty() {
+        anyhow::bail!("{} bytes left over after decoding", input.len());
+    }
+    Ok(())
+}
+
+/// Decodes a hex string with an optional `0x` prefix.
+fn decode_hex(input: &str) -> Result<Vec<u8>> {
+    let input = input.strip_prefix("0x").unwrap_or(input);
+    Ok(hex::decode(input)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Metadata of a contract with a single constructor and messages covering the supported
+    /// types.
+    pub(super) const METADATA: &str = r#"
+{
+    "metadataVersion": "0.1.0",
+    "spec": {
+        "constructors": [
+            {
+                "args": [{ "name": "init_value", "type": { "displayName": ["bool"], "type": 1 } }],
+                "name": ["new"],
+                "selector": "0x9bae9d5e"
+            }
+        ],
+        "events": [
+            {
+                "args": [
+                    {
+                        "indexed": true,
+                        "name": "from",
+                        "type": { "displayName": ["Option"], "type": 11 }
+                    },
+                    {
+                        "indexed": false,
+                        "name": "value",
+                        "type": { "displayName": ["Balance"], "type": 5 }
+                    }
+                ],
+                "name": "Transfer"
+            }
+        ],
+        "messages": [
+            {
+                "args": [
+                    { "name": "to", "type": { "displayName": ["AccountId"], "type": 2 } },
+                    { "name": "value", "type": { "displayName": ["Balance"], "type": 5 } }
+                ],
+                "mutates": true,
+                "name": ["Erc20", "transfer"],
+                "payable": false,
+                "returnType": null,
+                "selector": "0x84a15da1"
+            },
+            {
+                "args": [
+                    { "name": "name", "type": { "displayName": ["String"], "type": 6 } },
+                    { "name": "data", "type": { "displayName": ["Vec"], "type": 7 } },
+                    { "name": "amounts", "type": { "displayName": ["Vec"], "type": 8 } },
+                    { "name": "limit", "type": { "displayName": ["Option"], "type": 9 } }
+                ],
+                "mutates": false,
+                "name": ["misc"],
+                "payable": false,
+                "returnType": { "displayName": ["Option"], "type": 9 },
+                "selector": "0x00000001"
+            }
+        ]
+    },
+    "storage": {},
+    "types": [
+        { "def": { "primitive": "bool" } },
+        {
+            "def": { "composite": { "fields": [{ "type": 3 }] } },
+            "path": ["ink_env", "types", "AccountId"]
+        },
+        { "def": { "array": { "len": 32, "type": 4 } } },
+        { "def": { "primitive": "u8" } },
+        { "def": { "primitive": "u128" } },
+        { "def": { "primitive": "str" } },
+        { "def": { "sequence": { "type": 4 } } },
+        { "def": { "sequence": { "type": 10 } } },
+        {
+            "def": {
+                "variant": {
+                    "variants": [
+                        { "name": "None" },
+                        { "name": "Some", "fields": [{ "type": 10 }] }
+                    ]
+                }
+            },
+            "params": [10],
+            "path": ["Option"]
+        },
+        { "def": { "primitive": "u32" } },
+        {
+            "def": {
+                "variant": {
+                    "variants": [
+                        { "name": "None" },
+                        { "name": "Some", "fields": [{ "type": 2 }] }
+                    ]
+                }
+            },
+            "params": [2],
+            "path": ["Option"]
+        }
+    ]
+}
+"#;
+
+    fn ink_project() -> InkProject {
+        serde_json::from_str(METADATA).expect("metadata is valid")
+    }
+
+    #[test]
+    fn encodes_constructor_call() {
+        let encoded = ink_project().encode_constructor("new", &["true"]).unwrap();
+
+        assert_eq!(encoded, vec![0x9b, 0xae, 0x9d, 0x5e, 1]);
+    }
+
+    #[test]
+    fn encodes_trait_message_call_by_full_or_short_name() {
+        let project = ink_project();
+        let to = format!("0x{}", "01".repeat(32));
+        let args = [to.as_str(), "340282366920938463463374607431768211455"];
+
+        let encoded = project.encode_message("Erc20::transfer", &args).unwrap();
+
+        let mut expected = vec![0x84, 0xa1, 0x5d, 0xa1];
+        expected.extend_from_slice(&[1; 32]);
+        expected.extend_from_slice(&[0xff; 16]);
+        assert_eq!(encoded, expected);
+        assert_eq!(project.encode_message("transfer", &args).unwrap(), expected);
+    }
+
+    #[test]
+    fn encodes_strings_sequences_and_variants() {
+        let encoded = ink_project()
+            .encode_message("misc", &["ink", "0x0102", "[1, 2]", r#"{"Some": 7}"#])
+            .unwrap();
+
+        let expected = vec![
+            0, 0, 0, 1, // selector
+            12, b'i', b'n', b'k', // name
+            8, 1, 2, // data
+            8, 1, 0, 0, 0, 2, 0, 0, 0, // amounts
+            1, 7, 0, 0, 0, // limit
+        ];
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn rejects_mismatching_arguments() {
+        let project = ink_project();
+        let error = |result: Result<Vec<u8>>| format!("{:?}", result.unwrap_err());
+
+        assert!(error(project.encode_constructor("new", &["1"]))
+            .contains("Invalid argument `init_value`"));
+        assert!(error(project.encode_constructor("new", &[] as &[&str]))
+            .contains("'new' expects 1 arguments (init_value: bool), but 0 were supplied"));
+        assert!(error(project.encode_constructor("default", &["true"]))
+            .contains("No constructor named 'default', available constructors: new"));
+        assert!(error(project.encode_message("transfer", &["0x01", "1"]))
+            .contains("Expected 32 elements"));
+        assert!(
+            error(project.encode_message("misc", &["ink", "0x", "[1]", "-1"]))
+                .contains("Invalid argument `limit`")
+        );
+    }
+
+    #[test]
+    fn decodes_event() {
+        let mut data = vec![0, 1];
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(&u128::MAX.to_le_bytes());
+
+        let event = ink_project().decode_event(&data).unwrap();
+
+        assert_eq!(
+            event,
+            serde_json::json!({
+                "Transfer": {
+                    "from": { "Some": format!("0x{}", "01".repeat(32)) },
+                    "value": "340282366920938463463374607431768211455"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_return_value() {
+        let project = ink_project();
+
+        assert_eq!(
+            project.decode_return("misc", &[1, 7, 0, 0, 0]).unwrap(),
+            serde_json::json!({ "Some": 7 })
+        );
+        assert_eq!(project.decode_return("misc", &[0]).unwrap(), "None");
+        assert_eq!(project.decode_return("transfer", &[]).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn rejects_invalid_encoded_data() {
+        let project = ink_project();
+        let error = |result: Result<Value>| format!("{:?}", result.unwrap_err());
+
+        assert!(error(project.decode_event(&[1])).contains("Invalid event index 1"));
+        assert!(error(project.decode_event(&[0, 0])).contains("Invalid argument `value`"));
+        assert!(error(project.decode_return("misc", &[2])).contains("Invalid variant index 2"));
+        assert!(error(project.decode_return("misc", &[0, 0])).contains("1 bytes left over"));
+    }
+}
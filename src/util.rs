@@ -14,11 +14,35 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::Verbosity;
+use crate::{crate_metadata::CrateMetadata, workspace::ManifestPath, Verbosity};
 use anyhow::{Context, Result};
+use contract_metadata::ContractMetadata;
 use rustc_version::Channel;
 use std::path::PathBuf;
-use std::{ffi::OsStr, path::Path, process::Command};
+use std::{
+    ffi::OsStr,
+    fs,
+    io::Read,
+    path::Path,
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Whether the result of the command is output as JSON.
+static OUTPUT_JSON: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the JSON output mode.
+///
+/// In JSON output mode only the JSON result is printed to stdout, the progress is printed to
+/// stderr by [`progress!`].
+pub(crate) fn set_output_json(output_json: bool) {
+    OUTPUT_JSON.store(output_json, Ordering::Relaxed);
+}
+
+/// Returns `true` if the result of the command is output as JSON.
+pub(crate) fn output_json() -> bool {
+    OUTPUT_JSON.load(Ordering::Relaxed)
+}
 
 /// Check whether the current rust channel is valid: `nightly` is recommended.
 pub fn assert_channel() -> Result<()> {
@@ -38,33 +62,29 @@ pub fn assert_channel() -> Result<()> {
 
 /// Run cargo with the supplied args
 ///
+/// The environment variables in `env` are only set for the cargo process, a value of `None`
+/// removes the variable from its environment.
+///
 /// If successful, returns the stdout bytes
 pub(crate) fn invoke_cargo<I, S, P>(
     command: &str,
     args: I,
     working_dir: Option<P>,
     verbosity: Option<Verbosity>,
+    env: Vec<(&str, Option<&str>)>,
 ) -> Result<Vec<u8>>
 where
     I: IntoIterator<Item = S> + std::fmt::Debug,
     S: AsRef<OsStr>,
     P: AsRef<Path>,
 {
-    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
-    let mut cmd = Command::new(cargo);
-    if let Some(path) = working_dir {
-        log::debug!("Setting cargo working dir to '{}'", path.as_ref().display());
-        cmd.current_dir(path);
+    let mut cmd = cargo_cmd(command, args, working_dir, verbosity);
+    for (key, value) in env {
+        match value {
+            Some(value) => cmd.env(key, value),
+            None => cmd.env_remove(key),
+        };
     }
-
-    cmd.arg(command);
-    cmd.args(args);
-    match verbosity {
-        Some(Verbosity::Quiet) => cmd.arg("--quiet"),
-        Some(Verbosity::Verbose) => cmd.arg("--verbose"),
-        None => &mut cmd,
-    };
-
     log::info!("invoking cargo: {:?}", cmd);
 
     let child = cmd
@@ -85,6 +105,84 @@ where
     }
 }
 
+/// Returns a `cargo` [`Command`] with the supplied args, ready to be spawned.
+///
+/// Use [`invoke_cargo`] instead if the command is expected to succeed and only its stdout is of
+/// interest.
+pub(crate) fn cargo_cmd<I, S, P>(
+    command: &str,
+    args: I,
+    working_dir: Option<P>,
+    verbosity: Option<Verbosity>,
+) -> Command
+where
+    I: IntoIterator<Item = S> + std::fmt::Debug,
+    S: AsRef<OsStr>,
+    P: AsRef<Path>,
+{
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut cmd = Command::new(cargo);
+    if let Some(path) = working_dir {
+        log::debug!("Setting cargo working dir to '{}'", path.as_ref().display());
+        cmd.current_dir(path);
+    }
+
+    cmd.arg(command);
+    cmd.args(args);
+    match verbosity {
+        Some(Verbosity::Quiet) => cmd.arg("--quiet"),
+        Some(Verbosity::Verbose) => cmd.arg("--verbose"),
+        None => &mut cmd,
+    };
+    cmd
+}
+
+/// Returns the canonical `CARGO_HOME`, defaulting to `~/.cargo` like cargo does.
+pub(crate) fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".cargo"))
+        })
+        .and_then(|cargo_home| fs::canonicalize(cargo_home).ok())
+}
+
+/// Load the wasm blob from the specified path.
+///
+/// Defaults to the target contract wasm in the current project, inferred via the crate metadata.
+/// The wasm of a `.contract` bundle is taken from its `source.wasm` field.
+pub(crate) fn load_contract_code(path: Option<&PathBuf>) -> Result<Vec<u8>> {
+    let contract_wasm_path = match path {
+        Some(path) => path.clone(),
+        None => {
+            let metadata = CrateMetadata::collect(&ManifestPath::discover()?)?;
+            metadata.dest_wasm
+        }
+    };
+    log::info!("Contract code path: {}", contract_wasm_path.display());
+    let mut data = Vec::new();
+    let mut file = fs::File::open(&contract_wasm_path)
+        .context(format!("Failed to open {}", contract_wasm_path.display()))?;
+    file.read_to_end(&mut data)?;
+
+    if contract_wasm_path.extension() == Some(OsStr::new("contract")) {
+        let bundle: ContractMetadata = serde_json::from_slice(&data).context(format!(
+            "Failed to parse bundle {}",
+            contract_wasm_path.display()
+        ))?;
+        let wasm = bundle.source().wasm().ok_or_else(|| {
+            anyhow::anyhow!(
+                "The bundle {} contains no wasm",
+                contract_wasm_path.display()
+            )
+        })?;
+        return Ok(wasm.as_bytes().to_vec());
+    }
+    Ok(data)
+}
+
 /// Returns the base name of the path.
 pub(crate) fn base_name(path: &PathBuf) -> &str {
     path.file_name()
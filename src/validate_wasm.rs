@@ -0,0 +1,393 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::sandbox::FunctionNames;
+use anyhow::Result;
+use parity_wasm::elements::{External, ImportCountType, Instruction, Internal, Module};
+
+/// The module of the host functions available to contracts.
+const HOST_MODULE: &str = "seal0";
+
+/// The host functions of the `seal0` module of `pallet-contracts`.
+///
+/// Unlike the host functions of the sandbox, this includes the functions which are not
+/// emulated there.
+const HOST_FUNCTIONS: [&str; 33] = [
+    "seal_set_storage",
+    "seal_clear_storage",
+    "seal_get_storage",
+    "seal_transfer",
+    "seal_call",
+    "seal_instantiate",
+    "seal_terminate",
+    "seal_input",
+    "seal_return",
+    "seal_caller",
+    "seal_address",
+    "seal_weight_to_fee",
+    "seal_gas_left",
+    "seal_balance",
+    "seal_value_transferred",
+    "seal_random",
+    "seal_now",
+    "seal_minimum_balance",
+    "seal_tombstone_deposit",
+    "seal_restore_to",
+    "seal_deposit_event",
+    "seal_set_rent_allowance",
+    "seal_rent_allowance",
+    "seal_rent_params",
+    "seal_println",
+    "seal_debug_message",
+    "seal_block_number",
+    "seal_hash_sha2_256",
+    "seal_hash_keccak_256",
+    "seal_hash_blake2_256",
+    "seal_hash_blake2_128",
+    "seal_call_chain_extension",
+    "seal_ecdsa_recover",
+];
+
+/// The module and field of the memory imported by contracts.
+const MEMORY_IMPORT: (&str, &str) = ("env", "memory");
+
+/// The functions a contract must export.
+const EXPORTS: [&str; 2] = ["call", "deploy"];
+
+/// Validates that the Wasm of the contract can be deployed to a chain.
+///
+/// Rejects
+///
+/// - imports other than the `seal0` host functions and the `env.memory`,
+/// - a `start` section,
+/// - instructions operating on floats.
+///
+/// Those would only fail on-chain, with an error which is hard to track down. Invalid imports are
+/// reported together with the functions calling them, if the module has a `name` section.
+///
+/// This runs on the optimized Wasm, so unused functions are not reported. The exports are
+/// checked by [`validate_exports`] before.
+pub(crate) fn validate(module: &Module) -> Result<()> {
+    let names = FunctionNames::from_module(module);
+
+    let mut errors = validate_imports(module, &names);
+    if let Some(start) = module.start_section() {
+        errors.push(format!(
+            "Contracts must not have a start function, found `{}`",
+            names.name(start)
+        ));
+    }
+    errors.extend(validate_instructions(module, &names));
+
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "Validation of the contract's Wasm failed:\n  - {}",
+            errors.join("\n  - ")
+        );
+    }
+    Ok(())
+}
+
+/// Returns an error for every import which is neither a known host function nor the memory.
+fn validate_imports(module: &Module, names: &FunctionNames) -> Vec<String> {
+    let entries = match module.import_section() {
+        Some(section) => section.entries(),
+        None => return Vec::new(),
+    };
+
+    let mut errors = Vec::new();
+    let mut function_index = 0;
+    for entry in entries {
+        let valid = match entry.external() {
+            External::Function(_) => {
+                entry.module() == HOST_MODULE && HOST_FUNCTIONS.contains(&entry.field())
+            }
+            External::Memory(_) => (entry.module(), entry.field()) == MEMORY_IMPORT,
+            External::Global(_) | External::Table(_) => false,
+        };
+        if !valid {
+            let mut error = format!("Invalid import `{}.{}`", entry.module(), entry.field());
+            let callers = match entry.external() {
+                External::Function(_) if !names.is_empty() => callers(module, function_index),
+                _ => Vec::new(),
+            };
+            if !callers.is_empty() {
+                let callers = callers
+                    .into_iter()
+                    .map(|caller| format!("`{}`", names.name(caller)))
+                    .collect::<Vec<_>>();
+                error.push_str(&format!(", called by {}", callers.join(", ")));
+            }
+            errors.push(error);
+        }
+        if let External::Function(_) = entry.external() {
+            function_index += 1;
+        }
+    }
+    errors
+}
+
+/// Validates that the Wasm of the contract exports the `call` and `deploy` functions.
+///
+/// This has to run before the optimization, which removes all other exports and fails without
+/// an explanation if one of them is missing.
+pub(crate) fn validate_exports(module: &Module) -> Result<()> {
+    let exports = module
+        .export_section()
+        .map(|section| section.entries())
+        .unwrap_or_default();
+    let missing = EXPORTS
+        .iter()
+        .filter(|export| {
+            !exports.iter().any(|entry| {
+                entry.field() == **export && matches!(entry.internal(), Internal::Function(_))
+            })
+        })
+        .map(|export| format!("`{}`", export))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Validation of the contract's Wasm failed: missing the exported function {}, the \
+             contract has to use `#[ink::contract]`",
+            missing.join(" and ")
+        );
+    }
+    Ok(())
+}
+
+/// Returns an error for every function using floats, with the first float instruction found.
+fn validate_instructions(module: &Module, names: &FunctionNames) -> Vec<String> {
+    let bodies = match module.code_section() {
+        Some(section) => section.bodies(),
+        None => return Vec::new(),
+    };
+    let imported_functions = module.import_count(ImportCountType::Function);
+
+    bodies
+        .iter()
+        .enumerate()
+        .filter_map(|(index, body)| {
+            let instruction = body
+                .code()
+                .elements()
+                .iter()
+                .find(|instruction| is_float_instruction(instruction))?;
+            let function_index = (imported_functions + index) as u32;
+            Some(format!(
+                "Function `{}` uses the float instruction `{:?}`, floats are not supported",
+                names.name(function_index),
+                instruction
+            ))
+        })
+        .collect()
+}
+
+/// Returns the indices of the functions which call the function with the given index.
+fn callers(module: &Module, function_index: u32) -> Vec<u32> {
+    let imported_functions = module.import_count(ImportCountType::Function);
+    module
+        .code_section()
+        .map(|section| section.bodies())
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .filter(|(_, body)| {
+            body.code()
+                .elements()
+                .contains(&Instruction::Call(function_index))
+        })
+        .map(|(index, _)| (imported_functions + index) as u32)
+        .collect()
+}
+
+fn is_float_instruction(instruction: &Instruction) -> bool {
+    use Instruction::*;
+    matches!(
+        instruction,
+        F32Load(..)
+            | F64Load(..)
+            | F32Store(..)
+            | F64Store(..)
+            | F32Const(_)
+            | F64Const(_)
+            | F32Eq
+            | F32Ne
+            | F32Lt
+            | F32Gt
+            | F32Le
+            | F32Ge
+            | F64Eq
+            | F64Ne
+            | F64Lt
+            | F64Gt
+            | F64Le
+            | F64Ge
+            | F32Abs
+            | F32Neg
+            | F32Ceil
+            | F32Floor
+            | F32Trunc
+            | F32Nearest
+            | F32Sqrt
+            | F32Add
+            | F32Sub
+            | F32Mul
+            | F32Div
+            | F32Min
+            | F32Max
+            | F32Copysign
+            | F64Abs
+            | F64Neg
+            | F64Ceil
+            | F64Floor
+            | F64Trunc
+            | F64Nearest
+            | F64Sqrt
+            | F64Add
+            | F64Sub
+            | F64Mul
+            | F64Div
+            | F64Min
+            | F64Max
+            | F64Copysign
+            | I32TruncSF32
+            | I32TruncUF32
+            | I32TruncSF64
+            | I32TruncUF64
+            | I64TruncSF32
+            | I64TruncUF32
+            | I64TruncSF64
+            | I64TruncUF64
+            | F32ConvertSI32
+            | F32ConvertUI32
+            | F32ConvertSI64
+            | F32ConvertUI64
+            | F32DemoteF64
+            | F64ConvertSI32
+            | F64ConvertUI32
+            | F64ConvertSI64
+            | F64ConvertUI64
+            | F64PromoteF32
+            | I32ReinterpretF32
+            | I64ReinterpretF64
+            | F32ReinterpretI32
+            | F64ReinterpretI64
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(wat: &str) -> Module {
+        let wasm = wabt::Wat2Wasm::new()
+            .write_debug_names(true)
+            .convert(wat)
+            .expect("invalid wat");
+        parity_wasm::deserialize_buffer(wasm.as_ref()).expect("invalid wasm")
+    }
+
+    fn errors(wat: &str) -> String {
+        validate(&module(wat)).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn valid_contract_passes() {
+        let module = module(
+            r#"
+            (module
+                (import "seal0" "seal_input" (func $seal_input (param i32 i32)))
+                (import "env" "memory" (memory 1 16))
+                (func (export "call"))
+                (func (export "deploy") (call $seal_input (i32.const 0) (i32.const 0)))
+            )
+            "#,
+        );
+
+        assert!(validate_exports(&module).is_ok());
+        assert!(validate(&module).is_ok());
+    }
+
+    #[test]
+    fn invalid_imports_are_reported_with_their_callers() {
+        let errors = errors(
+            r#"
+            (module
+                (import "seal0" "seal_unknown" (func $seal_unknown))
+                (import "env" "abort" (func $abort))
+                (import "env" "table" (table 1 funcref))
+                (import "env" "memory" (memory 1 16))
+                (func $helper (call $seal_unknown))
+                (func (export "call") (call $helper))
+                (func (export "deploy") (call $seal_unknown))
+            )
+            "#,
+        );
+
+        assert_eq!(
+            errors,
+            "Validation of the contract's Wasm failed:\n  \
+             - Invalid import `seal0.seal_unknown`, called by `helper`, `func[4]`\n  \
+             - Invalid import `env.abort`\n  \
+             - Invalid import `env.table`"
+        );
+    }
+
+    #[test]
+    fn missing_entrypoints_are_rejected() {
+        let module = module(
+            r#"
+            (module
+                (import "env" "memory" (memory 1 16))
+                (global $heap_base i32 (i32.const 0))
+                (func (export "call"))
+                (export "deploy" (global $heap_base))
+                (export "__heap_base" (global $heap_base))
+            )
+            "#,
+        );
+
+        assert_eq!(
+            validate_exports(&module).unwrap_err().to_string(),
+            "Validation of the contract's Wasm failed: missing the exported function `deploy`, \
+             the contract has to use `#[ink::contract]`"
+        );
+    }
+
+    #[test]
+    fn start_and_floats_are_rejected() {
+        let errors = errors(
+            r#"
+            (module
+                (import "env" "memory" (memory 1 16))
+                (func $init)
+                (func $float (result f32) (f32.add (f32.const 1) (f32.const 2)))
+                (func (export "call"))
+                (func (export "deploy") (drop (call $float)))
+                (start $init)
+            )
+            "#,
+        );
+
+        assert_eq!(
+            errors,
+            "Validation of the contract's Wasm failed:\n  \
+             - Contracts must not have a start function, found `init`\n  \
+             - Function `float` uses the float instruction `F32Const(1065353216)`, floats are not \
+             supported"
+        );
+    }
+}
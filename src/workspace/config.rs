@@ -0,0 +1,160 @@
+// Copyright 2018-2021 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::manifest::{replace_value, table_keys, table_like_mut};
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::{Document, Item, Value};
+
+/// Copies the cargo config to the given path, rewriting relative paths to absolute paths of the
+/// original location.
+///
+/// Cargo resolves the paths of a config relative to the directory containing its `.cargo`
+/// directory, which only contains the manifests in the temporary workspace.
+///
+/// # Rewrites
+///
+/// - `paths`
+/// - `[source.<name>]/directory` and `local-registry`, e.g. of vendored sources
+/// - `[patch.<registry>]` dependency paths
+pub(super) fn copy_config(original: &Path, copy: &Path) -> Result<()> {
+    let contents =
+        fs::read_to_string(original).context(format!("Failed to read {}", original.display()))?;
+    let mut config = contents
+        .parse::<Document>()
+        .context(format!("Failed to parse {}", original.display()))?;
+    let base_dir = original
+        .parent()
+        .and_then(Path::parent)
+        .expect("Cargo configs are in a `.cargo` directory; qed");
+
+    let to_absolute = |item: &mut Item| {
+        if let Some(path) = item.as_str().map(PathBuf::from) {
+            if path.is_relative() {
+                let path = base_dir.join(path);
+                log::debug!("Rewriting config path to '{}'", path.display());
+                replace_value(item, path.to_string_lossy().as_ref());
+            }
+        }
+    };
+
+    let root = config.as_table_mut();
+
+    // Rewrite `paths = ["/path/to/override"]`
+    if let Some(paths) = root.get_mut("paths").and_then(Item::as_array_mut) {
+        for index in 0..paths.len() {
+            let path = paths.get(index).and_then(Value::as_str).map(PathBuf::from);
+            if let Some(path) = path.filter(|path| path.is_relative()) {
+                let path = base_dir.join(path);
+                let _ = paths.replace(index, path.to_string_lossy().as_ref());
+            }
+        }
+    }
+
+    // Rewrite `[source.vendored-sources] directory = /path/to/vendor`
+    if let Some(sources) = root.get_mut("source").and_then(table_like_mut) {
+        for name in table_keys(sources) {
+            if let Some(source) = sources.get_mut(&name).and_then(table_like_mut) {
+                for key in &["directory", "local-registry"] {
+                    if let Some(path) = source.get_mut(key) {
+                        to_absolute(path);
+                    }
+                }
+            }
+        }
+    }
+
+    // Rewrite `[patch.crates-io]` dependency paths
+    if let Some(patch) = root.get_mut("patch").and_then(table_like_mut) {
+        for registry in table_keys(patch) {
+            if let Some(dependencies) = patch.get_mut(&registry).and_then(table_like_mut) {
+                for name in table_keys(dependencies) {
+                    let path = dependencies
+                        .get_mut(&name)
+                        .and_then(table_like_mut)
+                        .and_then(|dependency| dependency.get_mut("path"));
+                    if let Some(path) = path {
+                        to_absolute(path);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(dir) = copy.parent() {
+        fs::create_dir_all(dir).context(format!("Creating directory '{}'", dir.display()))?;
+    }
+    fs::write(copy, config.to_string_in_original_order())
+        .context(format!("Failed to write {}", copy.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::with_tmp_dir;
+
+    #[test]
+    fn relative_paths_of_the_config_are_rewritten() {
+        with_tmp_dir(|path| {
+            let original = path.join("workspace").join(".cargo").join("config.toml");
+            fs::create_dir_all(original.parent().expect("the config has a parent"))?;
+            fs::write(
+                &original,
+                r#"paths = ["overrides/util", "/opt/overrides"]
+
+[source.crates-io]
+replace-with = "vendored-sources" # offline builds
+
+[source.vendored-sources]
+directory = "vendor"
+
+[patch.crates-io]
+ink_lang = { path = "../ink/crates/lang" }
+
+[net]
+offline = true
+"#,
+            )?;
+            let copy = path.join("copy").join(".cargo").join("config.toml");
+
+            copy_config(&original, &copy)?;
+
+            let dir = path.join("workspace");
+            let expected = format!(
+                r#"paths = ["{dir}/overrides/util", "/opt/overrides"]
+
+[source.crates-io]
+replace-with = "vendored-sources" # offline builds
+
+[source.vendored-sources]
+directory = "{dir}/vendor"
+
+[patch.crates-io]
+ink_lang = {{ path = "{dir}/../ink/crates/lang" }}
+
+[net]
+offline = true
+"#,
+                dir = dir.display()
+            );
+            assert_eq!(fs::read_to_string(&copy)?, expected);
+            Ok(())
+        })
+    }
+}
@@ -20,14 +20,33 @@ use super::{metadata, Profile};
 use std::convert::{TryFrom, TryInto};
 use std::{
     collections::HashSet,
-    fs,
+    env, fs,
     path::{Path, PathBuf},
 };
 use toml::value;
+use toml_edit::{decorated, Array, Document, Item, Table, TableLike, Value};
 
 const MANIFEST_FILE: &str = "Cargo.toml";
 const LEGACY_METADATA_PACKAGE_PATH: &str = ".ink/abi_gen";
 const METADATA_PACKAGE_PATH: &str = ".ink/metadata_gen";
+const BUILD_SCRIPT: &str = "build.rs";
+
+/// The dependency sections of a manifest, cargo accepts them with underscores as well.
+const DEPENDENCY_SECTIONS: [&str; 5] = [
+    "dependencies",
+    "dev-dependencies",
+    "dev_dependencies",
+    "build-dependencies",
+    "build_dependencies",
+];
+
+/// The arrays of targets besides the `[lib]`, with the directory of their default sources.
+const TARGET_ARRAYS: [(&str, &str); 4] = [
+    ("bin", "src/bin"),
+    ("test", "tests"),
+    ("example", "examples"),
+    ("bench", "benches"),
+];
 
 /// Path to a Cargo.toml file
 #[derive(Clone, Debug)]
@@ -49,6 +68,49 @@ impl ManifestPath {
         })
     }
 
+    /// Returns the manifest of the contract in the current directory, the way cargo finds it.
+    ///
+    /// This is the nearest `Cargo.toml` of the current directory or its parents. If that is the
+    /// manifest of a virtual workspace, the manifest of its single ink! contract member is used.
+    pub fn discover() -> Result<Self> {
+        let manifest_path = ManifestPath::nearest()?;
+        if !is_virtual_manifest(manifest_path.as_ref())? {
+            return Ok(manifest_path);
+        }
+        let contracts = crate::crate_metadata::workspace_contracts(&manifest_path, &[])?;
+        match &contracts[..] {
+            [contract] => ManifestPath::new(&contract.manifest_path),
+            _ => {
+                let names = contracts
+                    .iter()
+                    .map(|contract| contract.name.as_str())
+                    .collect::<Vec<_>>();
+                anyhow::bail!(
+                    "The workspace {} contains multiple ink! contracts: {}. \
+                     Select one of them with --manifest-path",
+                    manifest_path.as_ref().display(),
+                    names.join(", ")
+                )
+            }
+        }
+    }
+
+    /// Returns the nearest `Cargo.toml` of the current directory or its parents.
+    ///
+    /// Unlike [`ManifestPath::discover`] this does not resolve a virtual workspace manifest.
+    pub fn nearest() -> Result<Self> {
+        let current_dir = env::current_dir().context("Failed to get the current directory")?;
+        match nearest_manifest(&current_dir) {
+            Some(path) if path.parent() == Some(current_dir.as_path()) => Ok(Default::default()),
+            Some(path) => ManifestPath::new(path),
+            None => anyhow::bail!(
+                "Could not find `{}` in `{}` or any parent directory",
+                MANIFEST_FILE,
+                current_dir.display()
+            ),
+        }
+    }
+
     /// Create an arg `--manifest-path=` for `cargo` command
     pub fn cargo_arg(&self) -> String {
         format!("--manifest-path={}", self.path.to_string_lossy())
@@ -83,10 +145,26 @@ where
     type Error = anyhow::Error;
 
     fn try_from(value: Option<P>) -> Result<Self, Self::Error> {
-        value.map_or(Ok(Default::default()), ManifestPath::new)
+        value.map_or_else(ManifestPath::discover, ManifestPath::new)
     }
 }
 
+/// Returns the `Cargo.toml` in the given directory or the nearest of its ancestors.
+fn nearest_manifest(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(MANIFEST_FILE))
+        .find(|manifest| manifest.is_file())
+}
+
+/// Returns `true` if the manifest has a `[workspace]` but no `[package]` section.
+fn is_virtual_manifest(path: &Path) -> Result<bool> {
+    let contents =
+        fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    let toml: value::Table =
+        toml::from_str(&contents).context(format!("Failed to parse {}", path.display()))?;
+    Ok(toml.contains_key("workspace") && !toml.contains_key("package"))
+}
+
 impl Default for ManifestPath {
     fn default() -> ManifestPath {
         ManifestPath::new(MANIFEST_FILE).expect("it's a valid manifest file")
@@ -106,9 +184,12 @@ impl From<ManifestPath> for PathBuf {
 }
 
 /// Create, amend and save a copy of the specified `Cargo.toml`.
+///
+/// The manifest is edited as a `toml_edit` document, so that everything which is not amended keeps
+/// its formatting, comments and key order.
 pub struct Manifest {
     path: ManifestPath,
-    toml: value::Table,
+    toml: Document,
     /// True if a metadata package should be generated for this manifest
     metadata_package: bool,
 }
@@ -123,7 +204,10 @@ impl Manifest {
     {
         let manifest_path = path.try_into()?;
         let toml = fs::read_to_string(&manifest_path).context("Loading Cargo.toml")?;
-        let toml: value::Table = toml::from_str(&toml)?;
+        let toml = toml.parse::<Document>().context(format!(
+            "Failed to parse {}",
+            manifest_path.as_ref().display()
+        ))?;
 
         Ok(Manifest {
             path: manifest_path,
@@ -138,10 +222,12 @@ impl Manifest {
     }
 
     /// Get mutable reference to `[lib] crate-types = []` section
-    fn get_crate_types_mut(&mut self) -> Result<&mut value::Array> {
+    fn get_crate_types_mut(&mut self) -> Result<&mut Array> {
         let lib = self
             .toml
+            .as_table_mut()
             .get_mut("lib")
+            .and_then(table_like_mut)
             .ok_or_else(|| anyhow::anyhow!("lib section not found"))?;
         let crate_types = lib
             .get_mut("crate-type")
@@ -158,18 +244,17 @@ impl Manifest {
     pub fn with_added_crate_type(&mut self, crate_type: &str) -> Result<&mut Self> {
         let crate_types = self.get_crate_types_mut()?;
         if !crate_type_exists(crate_type, crate_types) {
-            crate_types.push(crate_type.into());
+            crate_types
+                .push(crate_type)
+                .map_err(|_| anyhow::anyhow!("crate-types should be an Array of strings"))?;
         }
         Ok(self)
     }
 
     /// Set `[profile.release]` lto flag
     pub fn with_profile_release_lto(&mut self, enabled: bool) -> Result<&mut Self> {
-        let lto = self
-            .get_profile_release_table_mut()?
-            .entry("lto")
-            .or_insert(enabled.into());
-        *lto = enabled.into();
+        let lto = self.get_profile_release_table_mut()?.entry("lto");
+        replace_value(lto, enabled);
         Ok(self)
     }
 
@@ -186,16 +271,18 @@ impl Manifest {
     }
 
     /// Get mutable reference to `[profile.release]` section
-    fn get_profile_release_table_mut(&mut self) -> Result<&mut value::Table> {
-        let profile = self
-            .toml
-            .entry("profile")
-            .or_insert(value::Value::Table(Default::default()));
+    fn get_profile_release_table_mut(&mut self) -> Result<&mut Table> {
+        let profile = self.toml.as_table_mut().entry("profile").or_insert({
+            // only the `[profile.release]` header is written for a new `[profile]` section
+            let mut profile = Table::new();
+            profile.set_implicit(true);
+            Item::Table(profile)
+        });
         let release = profile
             .as_table_mut()
             .ok_or_else(|| anyhow::anyhow!("profile should be a table"))?
             .entry("release")
-            .or_insert(value::Value::Table(Default::default()));
+            .or_insert(toml_edit::table());
         release
             .as_table_mut()
             .ok_or_else(|| anyhow::anyhow!("release should be a table"))
@@ -206,8 +293,24 @@ impl Manifest {
     /// If the value does not exist, does nothing.
     pub fn with_removed_crate_type(&mut self, crate_type: &str) -> Result<&mut Self> {
         let crate_types = self.get_crate_types_mut()?;
-        if crate_type_exists(crate_type, crate_types) {
-            crate_types.retain(|v| v.as_str().map_or(true, |s| s != crate_type));
+        loop {
+            let index = crate_types
+                .iter()
+                .position(|v| v.as_str() == Some(crate_type));
+            let index = match index {
+                Some(index) => index,
+                None => break,
+            };
+            let removed = crate_types.remove(index);
+            // the new first value takes over the formatting of the removed one, i.e. no space
+            // after the opening bracket
+            if index == 0 {
+                if let Some(first) = crate_types.get(0).cloned() {
+                    let first =
+                        decorated(first, removed.decor().prefix(), removed.decor().suffix());
+                    let _ = crate_types.replace_formatted(0, first);
+                }
+            }
         }
         Ok(self)
     }
@@ -216,20 +319,24 @@ impl Manifest {
     pub fn with_metadata_package(&mut self) -> Result<&mut Self> {
         let workspace = self
             .toml
+            .as_table_mut()
             .entry("workspace")
-            .or_insert(value::Value::Table(Default::default()));
+            .or_insert(toml_edit::table());
         let members = workspace
             .as_table_mut()
             .ok_or_else(|| anyhow::anyhow!("workspace should be a table"))?
             .entry("members")
-            .or_insert(value::Value::Array(Default::default()))
+            .or_insert(toml_edit::value(Array::default()))
             .as_array_mut()
             .ok_or_else(|| anyhow::anyhow!("members should be an array"))?;
 
-        if members.contains(&LEGACY_METADATA_PACKAGE_PATH.into()) {
+        if members
+            .iter()
+            .any(|member| member.as_str() == Some(LEGACY_METADATA_PACKAGE_PATH))
+        {
             // warn user if they have legacy metadata generation artifacts
             use colored::Colorize;
-            println!(
+            progress!(
                 "{} {} {} {}",
                 "warning:".yellow().bold(),
                 "please remove".bold(),
@@ -239,7 +346,9 @@ impl Manifest {
                     .bold()
             );
         } else {
-            members.push(METADATA_PACKAGE_PATH.into());
+            members
+                .push(METADATA_PACKAGE_PATH)
+                .map_err(|_| anyhow::anyhow!("members should be an array of strings"))?;
         }
 
         self.metadata_package = true;
@@ -252,8 +361,13 @@ impl Manifest {
     ///
     /// # Rewrites
     ///
-    /// - `[lib]/path`
-    /// - `[dependencies]`
+    /// - `[package]/build`, which is added if the implicit `build.rs` exists
+    /// - `[lib]/path`, `[[bin]]/path`, `[[test]]/path`, `[[example]]/path` and `[[bench]]/path`,
+    ///   which are added if not specified
+    /// - `[dependencies]`, `[dev-dependencies]` and `[build-dependencies]`, also those of the
+    ///   `[target.'cfg(..)']` sections
+    /// - `[patch.<registry>]` and `[replace]`
+    /// - `[workspace]/exclude`
     ///
     /// Dependencies with package names specified in `exclude_deps` will not be rewritten.
     pub(super) fn rewrite_relative_paths<I, S>(&mut self, exclude_deps: I) -> Result<&mut Self>
@@ -265,8 +379,12 @@ impl Manifest {
         let abs_dir = abs_path
             .parent()
             .expect("The manifest path is a file path so has a parent; qed");
+        let exclude = exclude_deps
+            .into_iter()
+            .map(|s| s.as_ref().to_string())
+            .collect::<HashSet<_>>();
 
-        let to_absolute = |value_id: String, existing_path: &mut value::Value| -> Result<()> {
+        let to_absolute = |value_id: String, existing_path: &mut Item| -> Result<()> {
             let path_str = existing_path
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("{} should be a string", value_id))?;
@@ -274,82 +392,168 @@ impl Manifest {
             if path.is_relative() {
                 let lib_abs = abs_dir.join(path);
                 log::debug!("Rewriting {} to '{}'", value_id, lib_abs.display());
-                *existing_path = value::Value::String(lib_abs.to_string_lossy().into())
+                replace_value(existing_path, lib_abs.to_string_lossy().as_ref());
             }
             Ok(())
         };
 
-        let rewrite_path = |table_value: &mut value::Value, table_section: &str, default: &str| {
-            let table = table_value.as_table_mut().ok_or_else(|| {
-                anyhow::anyhow!("'[{}]' section should be a table", table_section)
-            })?;
-
-            match table.get_mut("path") {
-                Some(existing_path) => {
+        let rewrite_path =
+            |table_value: &mut Table, table_section: &str, defaults: &[String]| match table_value
+                .get_mut("path")
+            {
+                Some(existing_path) if !existing_path.is_none() => {
                     to_absolute(format!("[{}]/path", table_section), existing_path)
                 }
-                None => {
-                    let default_path = PathBuf::from(default);
-                    if !default_path.exists() {
-                        anyhow::bail!(
-                            "No path specified, and the default `{}` was not found",
-                            default
-                        )
-                    }
-                    let path = abs_dir.join(default_path);
+                _ => {
+                    let path = defaults
+                        .iter()
+                        .map(|default| abs_dir.join(default))
+                        .find(|path| path.exists())
+                        .ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "No path specified, and the default `{}` was not found",
+                                defaults.join("` or `")
+                            )
+                        })?;
                     log::debug!("Adding default path '{}'", path.display());
-                    table.insert(
-                        "path".into(),
-                        value::Value::String(path.to_string_lossy().into()),
-                    );
+                    replace_value(table_value.entry("path"), path.to_string_lossy().as_ref());
                     Ok(())
                 }
+            };
+
+        let rewrite_dependencies = |dependencies: &mut Item, section: &str| -> Result<()> {
+            let table = table_like_mut(dependencies)
+                .ok_or_else(|| anyhow::anyhow!("{} should be a table", section))?;
+            for name in table_keys(table) {
+                let value = table
+                    .get_mut(&name)
+                    .expect("name is a key of the table; qed");
+                let package_name = match value["package"].as_str() {
+                    Some(package) => package.to_string(),
+                    None => package_name_of_spec(&name).to_string(),
+                };
+
+                if !exclude.contains(&package_name) {
+                    if let Some(dependency) = table_like_mut(value) {
+                        if let Some(dep_path) = dependency.get_mut("path") {
+                            to_absolute(format!("{} {}", section, package_name), dep_path)?;
+                        }
+                    }
+                }
             }
+            Ok(())
         };
 
+        let root = self.toml.as_table_mut();
+
+        // Rewrite `[package] build = /path/to/build.rs`
+        if let Some(package) = root.get_mut("package").and_then(Item::as_table_mut) {
+            let build = package.entry("build");
+            if build.is_str() {
+                to_absolute("[package]/build".into(), build)?;
+            } else if build.as_bool() != Some(false) {
+                let build_script = abs_dir.join(BUILD_SCRIPT);
+                if build_script.exists() {
+                    log::debug!("Adding build script '{}'", build_script.display());
+                    replace_value(build, build_script.to_string_lossy().as_ref());
+                }
+            }
+        }
+
         // Rewrite `[lib] path = /path/to/lib.rs`
-        if let Some(lib) = self.toml.get_mut("lib") {
-            rewrite_path(lib, "lib", "src/lib.rs")?;
+        if root.contains_key("lib") {
+            let lib = root["lib"]
+                .as_table_mut()
+                .ok_or_else(|| anyhow::anyhow!("'[lib]' section should be a table"))?;
+            rewrite_path(lib, "lib", &["src/lib.rs".into()])?;
         }
 
-        // Rewrite `[[bin]] path = /path/to/main.rs`
-        if let Some(bin) = self.toml.get_mut("bin") {
-            let bins = bin
-                .as_array_mut()
-                .ok_or_else(|| anyhow::anyhow!("'[[bin]]' section should be a table array"))?;
+        // Rewrite `[[bin]] path = /path/to/main.rs` and the paths of the other targets
+        for (section, dir) in &TARGET_ARRAYS {
+            if !root.contains_key(section) {
+                continue;
+            }
+            let targets = root[*section].as_array_of_tables_mut().ok_or_else(|| {
+                anyhow::anyhow!("'[[{}]]' section should be a table array", section)
+            })?;
 
-            // Rewrite `[[bin]] path =` value to an absolute path.
-            for bin in bins {
-                rewrite_path(bin, "[bin]", "src/main.rs")?;
+            for index in 0..targets.len() {
+                let target = targets.get_mut(index).expect("index is within bounds; qed");
+                let mut defaults = match target["name"].as_str() {
+                    Some(name) => vec![
+                        format!("{}/{}.rs", dir, name),
+                        format!("{}/{}/main.rs", dir, name),
+                    ],
+                    None => Vec::new(),
+                };
+                if *section == "bin" {
+                    defaults.push("src/main.rs".into());
+                }
+                rewrite_path(target, &format!("[{}]", section), &defaults)?;
             }
         }
 
         // Rewrite any dependency relative paths
-        if let Some(dependencies) = self.toml.get_mut("dependencies") {
-            let exclude = exclude_deps
-                .into_iter()
-                .map(|s| s.as_ref().to_string())
-                .collect::<HashSet<_>>();
-            let table = dependencies
-                .as_table_mut()
-                .ok_or_else(|| anyhow::anyhow!("dependencies should be a table"))?;
-            for (name, value) in table {
-                let package_name = {
-                    let package = value.get("package");
-                    let package_name = package.and_then(|p| p.as_str()).unwrap_or(name);
-                    package_name.to_string()
-                };
+        for section in &DEPENDENCY_SECTIONS {
+            if let Some(dependencies) = root.get_mut(section).filter(|item| !item.is_none()) {
+                rewrite_dependencies(dependencies, section)?;
+            }
+        }
 
-                if !exclude.contains(&package_name) {
-                    if let Some(dependency) = value.as_table_mut() {
-                        if let Some(dep_path) = dependency.get_mut("path") {
-                            to_absolute(format!("dependency {}", package_name), dep_path)?;
-                        }
+        // Rewrite `[target.'cfg(..)'.dependencies]`
+        if let Some(targets) = root.get_mut("target").and_then(table_like_mut) {
+            for target in table_keys(targets) {
+                let target_table = targets
+                    .get_mut(&target)
+                    .and_then(table_like_mut)
+                    .ok_or_else(|| anyhow::anyhow!("target.{} should be a table", target))?;
+                for section in &DEPENDENCY_SECTIONS {
+                    if let Some(dependencies) =
+                        target_table.get_mut(section).filter(|item| !item.is_none())
+                    {
+                        rewrite_dependencies(
+                            dependencies,
+                            &format!("target.{}.{}", target, section),
+                        )?;
                     }
                 }
             }
         }
 
+        // Rewrite `[patch.crates-io]` and the other patched registries
+        if let Some(patch) = root.get_mut("patch").and_then(table_like_mut) {
+            for registry in table_keys(patch) {
+                let dependencies = patch.get_mut(&registry).expect("registry is a key; qed");
+                rewrite_dependencies(dependencies, &format!("patch.{}", registry))?;
+            }
+        }
+
+        // Rewrite `[replace]`, whose keys are package id specs like `foo:0.1.0`
+        if let Some(replace) = root.get_mut("replace").filter(|item| !item.is_none()) {
+            rewrite_dependencies(replace, "replace")?;
+        }
+
+        // Rewrite `[workspace] exclude`, the excluded packages are not part of the copy
+        if let Some(excluded) = root
+            .get_mut("workspace")
+            .and_then(Item::as_table_mut)
+            .and_then(|workspace| workspace.get_mut("exclude"))
+            .and_then(Item::as_array_mut)
+        {
+            for index in 0..excluded.len() {
+                let path = excluded
+                    .get(index)
+                    .and_then(Value::as_str)
+                    .map(PathBuf::from)
+                    .ok_or_else(|| anyhow::anyhow!("[workspace]/exclude should be strings"))?;
+                if path.is_relative() {
+                    let path = abs_dir.join(path);
+                    log::debug!("Rewriting excluded '{}'", path.display());
+                    let _ = excluded.replace(index, path.to_string_lossy().as_ref());
+                }
+            }
+        }
+
         Ok(self)
     }
 
@@ -368,28 +572,19 @@ impl Manifest {
 
             fs::create_dir_all(&dir).context(format!("Creating directory '{}'", dir.display()))?;
 
-            let contract_package_name = self
-                .toml
-                .get("package")
-                .ok_or_else(|| anyhow::anyhow!("package section not found"))?
-                .get("name")
-                .ok_or_else(|| anyhow::anyhow!("[package] name field not found"))?
+            let contract_package_name = self.toml["package"]["name"]
                 .as_str()
-                .ok_or_else(|| anyhow::anyhow!("[package] name should be a string"))?;
+                .ok_or_else(|| anyhow::anyhow!("[package] name field not found"))?;
 
-            let ink_metadata = self
-                .toml
-                .get("dependencies")
-                .ok_or_else(|| anyhow::anyhow!("[dependencies] section not found"))?
-                .get("ink_metadata")
-                .ok_or_else(|| anyhow::anyhow!("ink_metadata dependency not found"))?
-                .as_table()
-                .ok_or_else(|| anyhow::anyhow!("ink_metadata dependency should be a table"))?;
+            let ink_metadata = &self.toml["dependencies"]["ink_metadata"];
+            if ink_metadata.is_none() {
+                anyhow::bail!("ink_metadata dependency not found")
+            }
 
             metadata::generate_package(dir, contract_package_name, ink_metadata.clone())?;
         }
 
-        let updated_toml = toml::to_string(&self.toml)?;
+        let updated_toml = self.toml.to_string_in_original_order();
         log::debug!(
             "Writing updated manifest to '{}'",
             manifest_path.as_ref().display()
@@ -399,8 +594,229 @@ impl Manifest {
     }
 }
 
-fn crate_type_exists(crate_type: &str, crate_types: &value::Array) -> bool {
-    crate_types
-        .iter()
-        .any(|v| v.as_str().map_or(false, |s| s == crate_type))
+fn crate_type_exists(crate_type: &str, crate_types: &Array) -> bool {
+    crate_types.iter().any(|v| v.as_str() == Some(crate_type))
+}
+
+/// Returns the keys of the table.
+pub(super) fn table_keys(table: &dyn TableLike) -> Vec<String> {
+    table.iter().map(|(key, _)| key.to_string()).collect()
+}
+
+/// Returns the package name of a dependency key or a package id spec of `[replace]`.
+///
+/// The spec is either `<name>:<version>` or `<url>#<name>:<version>`.
+fn package_name_of_spec(spec: &str) -> &str {
+    let spec = spec.rsplit('#').next().unwrap_or(spec);
+    spec.split(':').next().unwrap_or(spec)
+}
+
+/// Returns the table or inline table of the item.
+pub(super) fn table_like_mut(item: &mut Item) -> Option<&mut dyn TableLike> {
+    match item {
+        Item::Table(table) => Some(table),
+        Item::Value(Value::InlineTable(table)) => Some(table),
+        _ => None,
+    }
+}
+
+/// Replaces the value of the item, keeping the whitespace and comments around it.
+pub(super) fn replace_value<V: Into<Value>>(item: &mut Item, value: V) {
+    let value = match item.as_value() {
+        Some(existing) => decorated(
+            value.into(),
+            existing.decor().prefix(),
+            existing.decor().suffix(),
+        ),
+        None => decorated(value.into(), " ", ""),
+    };
+    *item = Item::Value(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::with_tmp_dir;
+
+    #[test]
+    fn nearest_manifest_of_a_nested_directory_is_found() {
+        with_tmp_dir(|path| {
+            let nested = path.join("workspace").join("contract").join("src");
+            fs::create_dir_all(&nested)?;
+            fs::write(path.join("workspace").join(MANIFEST_FILE), "[workspace]")?;
+            let contract_manifest = path.join("workspace").join("contract").join(MANIFEST_FILE);
+            fs::write(&contract_manifest, "[package]")?;
+
+            assert_eq!(nearest_manifest(&nested), Some(contract_manifest.clone()));
+            assert!(!is_virtual_manifest(&contract_manifest)?);
+            let workspace_manifest = nearest_manifest(&path.join("workspace"));
+            assert!(is_virtual_manifest(&workspace_manifest.unwrap())?);
+            Ok(())
+        })
+    }
+
+    /// Rewrites the relative paths of the manifest, with the given files next to it.
+    ///
+    /// The absolute directory of the manifest is replaced with `<dir>` in the result.
+    fn rewrite_relative_paths(manifest: &str, files: &[&str]) -> String {
+        let mut rewritten = String::new();
+        with_tmp_dir(|path| {
+            let manifest_path = path.join(MANIFEST_FILE);
+            fs::write(&manifest_path, manifest)?;
+            for file in files {
+                let file = path.join(file);
+                fs::create_dir_all(file.parent().expect("files are in the tmp dir"))?;
+                fs::write(file, "")?;
+            }
+
+            let mut manifest = Manifest::new(&manifest_path)?;
+            manifest.rewrite_relative_paths(&["member"])?;
+            let dir = fs::canonicalize(path)?;
+            rewritten = manifest
+                .toml
+                .to_string_in_original_order()
+                .replace(dir.to_string_lossy().as_ref(), "<dir>");
+            Ok(())
+        });
+        rewritten
+    }
+
+    #[test]
+    fn relative_paths_of_all_manifest_layouts_are_rewritten() {
+        let layouts = [
+            (
+                "build script",
+                "[package]\nname = \"flipper\"\nbuild = \"scripts/build.rs\"\n",
+                &[][..],
+                "[package]\nname = \"flipper\"\nbuild = \"<dir>/scripts/build.rs\"\n",
+            ),
+            (
+                "implicit build script",
+                "[package]\nname = \"flipper\"\n",
+                &["build.rs"][..],
+                "[package]\nname = \"flipper\"\nbuild = \"<dir>/build.rs\"\n",
+            ),
+            (
+                "disabled build script",
+                "[package]\nname = \"flipper\"\nbuild = false\n",
+                &["build.rs"][..],
+                "[package]\nname = \"flipper\"\nbuild = false\n",
+            ),
+            (
+                "default target paths",
+                "[lib]\n\n[[bin]]\nname = \"cli\"\n\n[[test]]\nname = \"e2e\"\n",
+                &["src/lib.rs", "src/bin/cli/main.rs", "tests/e2e.rs"][..],
+                "[lib]\npath = \"<dir>/src/lib.rs\"\n\n[[bin]]\nname = \"cli\"\n\
+                 path = \"<dir>/src/bin/cli/main.rs\"\n\n[[test]]\nname = \"e2e\"\n\
+                 path = \"<dir>/tests/e2e.rs\"\n",
+            ),
+            (
+                "dev and build dependencies",
+                "[dev-dependencies]\nutil = { path = \"../util\" }\n\n\
+                 [build-dependencies.gen]\npath = \"gen\"\n",
+                &[][..],
+                "[dev-dependencies]\nutil = { path = \"<dir>/../util\" }\n\n\
+                 [build-dependencies.gen]\npath = \"<dir>/gen\"\n",
+            ),
+            (
+                "target specific dependencies",
+                "[target.'cfg(unix)'.dependencies]\nutil = { path = \"../util\" }\n\n\
+                 [target.wasm32-unknown-unknown.dev-dependencies]\nmember = { path = \"../member\" }\n",
+                &[][..],
+                "[target.'cfg(unix)'.dependencies]\nutil = { path = \"<dir>/../util\" }\n\n\
+                 [target.wasm32-unknown-unknown.dev-dependencies]\nmember = { path = \"../member\" }\n",
+            ),
+            (
+                "patch and replace",
+                "[patch.crates-io]\nink_lang = { path = \"../ink/crates/lang\" }\n\n\
+                 [replace]\n\"util:0.1.0\" = { path = \"vendor/util\" }\n\
+                 \"member:0.1.0\" = { path = \"member\" }\n",
+                &[][..],
+                "[patch.crates-io]\nink_lang = { path = \"<dir>/../ink/crates/lang\" }\n\n\
+                 [replace]\n\"util:0.1.0\" = { path = \"<dir>/vendor/util\" }\n\
+                 \"member:0.1.0\" = { path = \"member\" }\n",
+            ),
+            (
+                "workspace exclude",
+                "[workspace]\nmembers = [\"member\"]\nexclude = [\"legacy\", \"/opt/other\"]\n",
+                &[][..],
+                "[workspace]\nmembers = [\"member\"]\nexclude = [\"<dir>/legacy\", \"/opt/other\"]\n",
+            ),
+        ];
+
+        for (layout, manifest, files, expected) in layouts.iter() {
+            assert_eq!(
+                rewrite_relative_paths(manifest, files),
+                *expected,
+                "layout: {}",
+                layout
+            );
+        }
+    }
+
+    #[test]
+    fn amending_the_manifest_preserves_its_formatting() {
+        with_tmp_dir(|path| {
+            let manifest_path = path.join(MANIFEST_FILE);
+            fs::write(
+                &manifest_path,
+                r#"[package]
+name = "flipper"
+version = "0.1.0"
+
+[dependencies]
+# the ink! dependencies
+ink_lang = { version = "3.0.0-rc2", default-features = false }
+util = { path = "../util", features = ["std"] } # local helpers
+ink_prelude = "3.0.0-rc2"
+
+[lib]
+name = "flipper"
+path = "lib.rs"
+crate-type = ["rlib", "cdylib"]
+
+[profile.release]
+overflow-checks = false # checked in tests
+"#,
+            )?;
+
+            let mut manifest = Manifest::new(&manifest_path)?;
+            manifest
+                .with_removed_crate_type("rlib")?
+                .with_profile_release_defaults(Profile::default_contract_release())?
+                .rewrite_relative_paths(Vec::<String>::new())?;
+            let written = ManifestPath::new(path.join("written").join(MANIFEST_FILE))?;
+            manifest.write(&written)?;
+
+            let dir = fs::canonicalize(path)?;
+            let expected = format!(
+                r#"[package]
+name = "flipper"
+version = "0.1.0"
+
+[dependencies]
+# the ink! dependencies
+ink_lang = {{ version = "3.0.0-rc2", default-features = false }}
+util = {{ path = "{}", features = ["std"] }} # local helpers
+ink_prelude = "3.0.0-rc2"
+
+[lib]
+name = "flipper"
+path = "{}"
+crate-type = ["cdylib"]
+
+[profile.release]
+overflow-checks = false # checked in tests
+opt-level = "z"
+lto = "fat"
+codegen-units = 1
+panic = "abort"
+"#,
+                dir.join("../util").display(),
+                dir.join("lib.rs").display()
+            );
+            assert_eq!(fs::read_to_string(&written)?, expected);
+            Ok(())
+        })
+    }
 }
@@ -16,7 +16,7 @@
 
 use anyhow::Result;
 use std::{fs, path::Path};
-use toml::value;
+use toml_edit::{Document, Item, Value};
 
 /// Generates a cargo workspace package `metadata-gen` which will be invoked via `cargo run` to
 /// generate contract metadata.
@@ -28,7 +28,7 @@ use toml::value;
 pub(super) fn generate_package<P: AsRef<Path>>(
     target_dir: P,
     contract_package_name: &str,
-    mut ink_metadata_dependency: value::Table,
+    mut ink_metadata_dependency: Item,
 ) -> Result<()> {
     let dir = target_dir.as_ref();
     log::debug!(
@@ -40,29 +40,33 @@ pub(super) fn generate_package<P: AsRef<Path>>(
     let cargo_toml = include_str!("../../templates/tools/generate-metadata/_Cargo.toml");
     let main_rs = include_str!("../../templates/tools/generate-metadata/main.rs");
 
-    let mut cargo_toml: value::Table = toml::from_str(cargo_toml)?;
-    let deps = cargo_toml
-        .get_mut("dependencies")
-        .expect("[dependencies] section specified in the template")
+    let mut cargo_toml = cargo_toml.parse::<Document>()?;
+    let deps = cargo_toml["dependencies"]
         .as_table_mut()
         .expect("[dependencies] is a table specified in the template");
 
     // initialize contract dependency
-    let contract = deps
-        .get_mut("contract")
-        .expect("contract dependency specified in the template")
-        .as_table_mut()
-        .expect("contract dependency is a table specified in the template");
-    contract.insert("package".into(), contract_package_name.into());
+    let contract = deps["contract"]
+        .as_inline_table_mut()
+        .expect("contract dependency is an inline table specified in the template");
+    contract.get_or_insert("package", contract_package_name);
+    contract.fmt();
 
     // make ink_metadata dependency use default features
-    ink_metadata_dependency.remove("default-features");
-    ink_metadata_dependency.remove("features");
-    ink_metadata_dependency.remove("optional");
+    let removed_keys = ["default-features", "features", "optional"];
+    match &mut ink_metadata_dependency {
+        Item::Table(table) => removed_keys.iter().for_each(|key| {
+            table.remove(key);
+        }),
+        Item::Value(Value::InlineTable(table)) => removed_keys.iter().for_each(|key| {
+            table.remove(key);
+        }),
+        _ => (),
+    }
 
     // add ink dependencies copied from contract manifest
-    deps.insert("ink_metadata".into(), ink_metadata_dependency.into());
-    let cargo_toml = toml::to_string(&cargo_toml)?;
+    deps["ink_metadata"] = ink_metadata_dependency;
+    let cargo_toml = cargo_toml.to_string();
 
     fs::write(dir.join("Cargo.toml"), cargo_toml)?;
     fs::write(dir.join("main.rs"), main_rs)?;
@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
+mod config;
 mod manifest;
 mod metadata;
 mod profile;
@@ -24,14 +25,22 @@ pub use self::{
     profile::Profile,
 };
 
-use anyhow::Result;
+use crate::util;
+use anyhow::{Context, Result};
 use cargo_metadata::{Metadata as CargoMetadata, Package, PackageId};
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
+    fs,
     path::{Path, PathBuf},
 };
 
+/// The cargo config files, which configure e.g. registries, source replacements and the network.
+const CONFIG_FILES: [&str; 2] = [".cargo/config", ".cargo/config.toml"];
+
+/// The files pinning the toolchain used by rustup.
+const TOOLCHAIN_FILES: [&str; 2] = ["rust-toolchain", "rust-toolchain.toml"];
+
 /// Make a copy of a cargo workspace, maintaining only the directory structure and manifest
 /// files. Relative paths to source files and non-workspace dependencies are rewritten to absolute
 /// paths to the original locations.
@@ -42,6 +51,7 @@ pub struct Workspace {
     workspace_root: PathBuf,
     root_package: PackageId,
     members: HashMap<PackageId, (Package, Manifest)>,
+    keep_temp: bool,
 }
 
 impl Workspace {
@@ -76,6 +86,7 @@ impl Workspace {
             workspace_root: metadata.workspace_root.clone(),
             root_package: root_package.clone(),
             members,
+            keep_temp: false,
         })
     }
 
@@ -121,6 +132,12 @@ impl Workspace {
         Ok(self)
     }
 
+    /// Keep the temporary directory of [`Workspace::using_temp`] instead of cleaning it up.
+    pub fn keep_temp(&mut self, keep: bool) -> &mut Self {
+        self.keep_temp = keep;
+        self
+    }
+
     /// Generates a package to invoke for generating contract metadata
     pub(super) fn with_metadata_gen_package(&mut self) -> Result<&mut Self> {
         self.with_workspace_manifest(|manifest| {
@@ -135,6 +152,9 @@ impl Workspace {
     /// Relative paths will be rewritten to absolute paths from the original workspace root, except
     /// intra-workspace relative dependency paths which will be preserved.
     ///
+    /// The `Cargo.lock`, cargo configs and toolchain files of the workspace are copied as well, so
+    /// that the build resolves the same dependencies with the same toolchain.
+    ///
     /// Returns the paths of the new manifests.
     pub fn write<P: AsRef<Path>>(&mut self, target: P) -> Result<Vec<(PackageId, ManifestPath)>> {
         let exclude_member_package_names = self
@@ -154,11 +174,49 @@ impl Workspace {
 
             new_manifest_paths.push((package_id.clone(), new_manifest));
         }
+        self.copy_workspace_files(target.as_ref())?;
         Ok(new_manifest_paths)
     }
 
+    /// Copies the `Cargo.lock` of the workspace, and the cargo configs and toolchain files of the
+    /// workspace root, the member directories and the directories in between, to the `target`.
+    fn copy_workspace_files(&self, target: &Path) -> Result<()> {
+        let copy = |original: &Path, copy: &Path| -> Result<()> {
+            log::debug!("Copying '{}' to '{}'", original.display(), copy.display());
+            fs::copy(original, copy).context(format!("Failed to copy {}", original.display()))?;
+            Ok(())
+        };
+
+        let lockfile = self.workspace_root.join("Cargo.lock");
+        if lockfile.exists() {
+            copy(&lockfile, &target.join("Cargo.lock"))?;
+        }
+
+        let members = self.members.values().map(|(package, _)| package);
+        for dir in config_dirs(&self.workspace_root, members) {
+            copy_config_files(dir, &target.join(dir.strip_prefix(&self.workspace_root)?))?;
+        }
+        Ok(())
+    }
+
+    /// Copies the cargo configs and toolchain files of the directories above the workspace root,
+    /// up to the `base` directory, to the same location relative to `target`, the copy of `base`.
+    fn copy_ancestor_files(&self, base: &Path, target: &Path) -> Result<()> {
+        let ancestors = self
+            .workspace_root
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(base));
+        for dir in ancestors {
+            copy_config_files(dir, &target.join(dir.strip_prefix(base)?))?;
+        }
+        Ok(())
+    }
+
     /// Copy the workspace with amended manifest files to a temporary directory, executing the
     /// supplied function with the root manifest path before the directory is cleaned up.
+    ///
+    /// The directory is kept, and its path printed, if enabled with [`Workspace::keep_temp`].
     pub fn using_temp<F>(&mut self, f: F) -> Result<()>
     where
         F: FnOnce(&ManifestPath) -> Result<()>,
@@ -167,7 +225,21 @@ impl Workspace {
             .prefix("cargo-contract_")
             .tempdir()?;
         log::debug!("Using temp workspace at '{}'", tmp_dir.path().display());
-        let new_paths = self.write(&tmp_dir)?;
+        // the workspace is nested below the copies of its ancestors with cargo configs or
+        // toolchain files, so these apply to the build like to the original workspace
+        let base = self
+            .workspace_root
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !config_files(dir).is_empty())
+            .last()
+            .unwrap_or(&self.workspace_root)
+            .to_path_buf();
+        let target = tmp_dir
+            .path()
+            .join(self.workspace_root.strip_prefix(&base)?);
+        let new_paths = self.write(&target)?;
+        self.copy_ancestor_files(&base, tmp_dir.path())?;
         let root_manifest_path = new_paths
             .iter()
             .find_map(|(pid, path)| {
@@ -178,6 +250,115 @@ impl Workspace {
                 }
             })
             .expect("root package should be a member of the temp workspace");
-        f(root_manifest_path)
+        let result = f(root_manifest_path);
+        if self.keep_temp {
+            let path = tmp_dir.into_path();
+            progress!("Kept the temporary workspace at {}", path.display());
+        }
+        result
+    }
+}
+
+/// Returns the cargo configs and toolchain files of the workspace and the directories above it,
+/// which are carried into the temporary workspace.
+pub fn workspace_config_files(metadata: &CargoMetadata) -> Vec<PathBuf> {
+    let members = metadata
+        .packages
+        .iter()
+        .filter(|package| metadata.workspace_members.contains(&package.id));
+    config_dirs(&metadata.workspace_root, members)
+        .into_iter()
+        .chain(metadata.workspace_root.ancestors().skip(1))
+        .flat_map(config_files)
+        .collect()
+}
+
+/// Returns the directories whose cargo configs and toolchain files apply to the workspace, i.e.
+/// the workspace root, the member directories and the directories in between.
+fn config_dirs<'a>(
+    workspace_root: &Path,
+    members: impl Iterator<Item = &'a Package>,
+) -> BTreeSet<&'a Path> {
+    members
+        .filter_map(|package| package.manifest_path.parent())
+        .flat_map(|dir| {
+            dir.ancestors()
+                .take_while(|dir| dir.starts_with(workspace_root))
+        })
+        .collect()
+}
+
+/// Returns the cargo configs and toolchain files in the directory.
+///
+/// The config in `CARGO_HOME` is skipped, since cargo reads it for the temporary workspace as
+/// well.
+fn config_files(dir: &Path) -> Vec<PathBuf> {
+    let is_cargo_home = |file: &Path| {
+        let cargo_dir = file.parent().and_then(|dir| fs::canonicalize(dir).ok());
+        cargo_dir.is_some() && cargo_dir == util::cargo_home()
+    };
+    CONFIG_FILES
+        .iter()
+        .chain(TOOLCHAIN_FILES.iter())
+        .map(|file| dir.join(file))
+        .filter(|file| file.is_file())
+        .filter(|file| !(file.starts_with(dir.join(".cargo")) && is_cargo_home(file)))
+        .collect()
+}
+
+/// Copies the cargo configs and toolchain files of the directory to the `target` directory.
+fn copy_config_files(dir: &Path, target: &Path) -> Result<()> {
+    for original in config_files(dir) {
+        let copy = target.join(original.strip_prefix(dir)?);
+        log::debug!("Copying '{}' to '{}'", original.display(), copy.display());
+        if original.starts_with(dir.join(".cargo")) {
+            config::copy_config(&original, &copy)?;
+        } else {
+            fs::copy(&original, &copy).context(format!("Failed to copy {}", original.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::with_tmp_dir;
+
+    #[test]
+    fn configs_above_the_workspace_root_apply_to_the_temp_workspace() {
+        with_tmp_dir(|path| {
+            let root = path.join("contracts").join("flipper");
+            fs::create_dir_all(root.join("src"))?;
+            fs::create_dir_all(path.join(".cargo"))?;
+            fs::write(
+                root.join("Cargo.toml"),
+                "[package]\nname = \"flipper\"\nversion = \"0.1.0\"\n",
+            )?;
+            fs::write(root.join("src").join("lib.rs"), "")?;
+            fs::write(
+                path.join(".cargo").join("config.toml"),
+                "[net]\noffline = true\n",
+            )?;
+            fs::write(path.join("rust-toolchain"), "nightly\n")?;
+
+            let metadata = cargo_metadata::MetadataCommand::new()
+                .manifest_path(root.join("Cargo.toml"))
+                .no_deps()
+                .exec()?;
+            let mut workspace = Workspace::new(&metadata, &metadata.workspace_members[0])?;
+
+            workspace.using_temp(|manifest_path| {
+                let temp_root = manifest_path
+                    .directory()
+                    .and_then(Path::parent)
+                    .and_then(Path::parent)
+                    .expect("the workspace is nested in the temp dir");
+                let config = fs::read_to_string(temp_root.join(".cargo").join("config.toml"))?;
+                assert_eq!(config, "[net]\noffline = true\n");
+                assert!(temp_root.join("rust-toolchain").is_file());
+                Ok(())
+            })
+        })
     }
 }
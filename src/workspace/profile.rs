@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
-use toml::value;
+use toml_edit::{Table, Value};
 
 /// Subset of cargo profile settings to configure defaults for building contracts
 pub struct Profile {
@@ -43,16 +43,16 @@ impl Profile {
     /// Therefore:
     ///   - If the user has explicitly defined a profile setting, it will not be overwritten.
     ///   - If a profile setting is not defined, the value from this profile instance will be added
-    pub(super) fn merge(&self, profile: &mut value::Table) {
-        let mut set_value_if_vacant = |key: &'static str, value: value::Value| {
+    pub(super) fn merge(&self, profile: &mut Table) {
+        let mut set_value_if_vacant = |key: &'static str, value: Value| {
             if !profile.contains_key(key) {
-                profile.insert(key.into(), value);
+                profile[key] = toml_edit::value(value);
             }
         };
         set_value_if_vacant("opt-level", self.opt_level.to_toml_value());
         set_value_if_vacant("lto", self.lto.to_toml_value());
         if let Some(codegen_units) = self.codegen_units {
-            set_value_if_vacant("codegen-units", codegen_units.into());
+            set_value_if_vacant("codegen-units", i64::from(codegen_units).into());
         }
         set_value_if_vacant("overflow-checks", self.overflow_checks.into());
         set_value_if_vacant("panic", self.panic.to_toml_value());
@@ -72,7 +72,7 @@ pub enum OptLevel {
 }
 
 impl OptLevel {
-    fn to_toml_value(&self) -> value::Value {
+    fn to_toml_value(&self) -> Value {
         match self {
             OptLevel::NoOptimizations => 0.into(),
             OptLevel::O1 => 1.into(),
@@ -99,7 +99,7 @@ pub enum Lto {
 }
 
 impl Lto {
-    fn to_toml_value(&self) -> value::Value {
+    fn to_toml_value(&self) -> Value {
         match self {
             Lto::ThinLocal => false.into(),
             Lto::Fat => "fat".into(),
@@ -118,7 +118,7 @@ pub enum PanicStrategy {
 }
 
 impl PanicStrategy {
-    fn to_toml_value(&self) -> value::Value {
+    fn to_toml_value(&self) -> Value {
         match self {
             PanicStrategy::Unwind => "unwind".into(),
             PanicStrategy::Abort => "abort".into(),
@@ -130,49 +130,43 @@ impl PanicStrategy {
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
+    use toml_edit::Document;
 
-    #[test]
-    fn merge_profile_inserts_preferred_defaults() {
+    fn merge(manifest_toml: &str) -> String {
         let profile = Profile::default_contract_release();
+        let mut manifest = manifest_toml.parse::<Document>().unwrap();
 
-        // no `[profile.release]` section specified
-        let manifest_toml = "";
-        let mut expected = toml::value::Table::new();
-        expected.insert("opt-level".into(), value::Value::String("z".into()));
-        expected.insert("lto".into(), value::Value::String("fat".into()));
-        expected.insert("codegen-units".into(), value::Value::Integer(1));
-        expected.insert("overflow-checks".into(), value::Value::Boolean(true));
-        expected.insert("panic".into(), value::Value::String("abort".into()));
+        let release = manifest["profile"]["release"].as_table_mut().unwrap();
+        profile.merge(release);
 
-        let mut manifest_profile = toml::from_str(manifest_toml).unwrap();
-
-        profile.merge(&mut manifest_profile);
+        manifest.to_string_in_original_order()
+    }
 
-        assert_eq!(expected, manifest_profile)
+    #[test]
+    fn merge_profile_inserts_preferred_defaults() {
+        // empty `[profile.release]` section specified
+        let manifest_toml = "[profile.release]\n";
+        let expected = r#"[profile.release]
+opt-level = "z"
+lto = "fat"
+codegen-units = 1
+overflow-checks = true
+panic = "abort"
+"#;
+
+        assert_eq!(expected, merge(manifest_toml))
     }
 
     #[test]
     fn merge_profile_preserves_user_defined_settings() {
-        let profile = Profile::default_contract_release();
-
-        let manifest_toml = r#"
-            panic = "unwind"
-            lto = false
-            opt-level = 3
-            overflow-checks = false
-            codegen-units = 256
-        "#;
-        let mut expected = toml::value::Table::new();
-        expected.insert("opt-level".into(), value::Value::Integer(3));
-        expected.insert("lto".into(), value::Value::Boolean(false));
-        expected.insert("codegen-units".into(), value::Value::Integer(256));
-        expected.insert("overflow-checks".into(), value::Value::Boolean(false));
-        expected.insert("panic".into(), value::Value::String("unwind".into()));
-
-        let mut manifest_profile = toml::from_str(manifest_toml).unwrap();
-
-        profile.merge(&mut manifest_profile);
-
-        assert_eq!(expected, manifest_profile)
+        let manifest_toml = r#"[profile.release]
+panic = "unwind"
+lto = false # the size is not relevant
+opt-level = 3
+overflow-checks = false
+codegen-units = 256
+"#;
+
+        assert_eq!(manifest_toml, merge(manifest_toml))
     }
 }